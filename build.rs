@@ -0,0 +1,4 @@
+// The embed feature copies the public folder in the executable, so it is rebuilt when it changes
+fn main() {
+    println!("cargo:rerun-if-changed=public");
+}
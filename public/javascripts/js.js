@@ -1,45 +1,246 @@
-function evalLinks(element) {
-  let links = element.textContent.match(/https?:\/\/[^\s]+/g);
-  if (links) {
-    links.forEach((link) => {
-      element.innerHTML = element.innerHTML.replace(
-        link,
-        `<a href="${link}" target="_blank" class="link text-primary">${link}</a>`,
-      );
-    });
-  }
+let reactionKinds = [];
+
+async function loadReactionKinds() {
+  let response = await fetch("/api/reactions");
+  reactionKinds = await response.json();
+}
+
+function updateReactionCounts(container, reactions) {
+  container.querySelectorAll("[data-reaction]").forEach((button) => {
+    button.querySelector(".badge").textContent =
+      reactions[button.dataset.reaction] || 0;
+  });
 }
 
-async function addReaction(reaction, id) {
+function updateReactions(container, reactions, userReaction) {
+  container.querySelectorAll("[data-reaction]").forEach((button) => {
+    button.classList.toggle("btn-outline", userReaction === button.dataset.reaction);
+  });
+  updateReactionCounts(container, reactions);
+}
+
+async function addReaction(target, id, reaction, container) {
   let response = await fetch("/api/reaction", {
     credentials: "same-origin",
     method: "POST",
-    body: `reaction=${reaction}&post_id=${id}`,
+    body: `reaction=${reaction}&${target}_id=${id}`,
   });
   let data = await response.json();
   if (data.status === "ok") {
-    let reactions = document.getElementById(id).querySelector("#reactions");
-    reactions.childNodes.forEach((reaction) => {
-      if (reaction.classList.contains("btn-outline")) {
-        let reactionCount = reaction.querySelector(".badge");
-        reactionCount.textContent = parseInt(reactionCount.textContent) - 1;
-        reaction.classList.remove("btn-outline");
-      }
-    });
-    let reactionElement = reactions.querySelector(`.${reaction}`);
-    reactionElement.classList.add("btn-outline");
-    let reactionCount = reactionElement.querySelector(".badge");
-    reactionCount.textContent = parseInt(reactionCount.textContent) + 1;
+    updateReactions(container, data.reactions, data.reaction);
+    container.dataset.reactorsLoaded = "";
   }
 }
 
-async function getReaction(id) {
+async function showReactors(target, id, container) {
+  if (container.dataset.reactorsLoaded) {
+    return;
+  }
+  container.dataset.reactorsLoaded = "true";
+  let response = await fetch(`/api/reactors?${target}_id=${id}`);
+  let reactors = await response.json();
+  container.querySelectorAll("[data-reaction]").forEach((button) => {
+    let kind = reactionKinds.find((kind) => kind.name === button.dataset.reaction);
+    let names = reactors
+      .filter((reactor) => reactor.type === button.dataset.reaction)
+      .map((reactor) => reactor.display_name);
+    button.title = names.length > 0 ? `${kind.label}: ${names.join(", ")}` : kind.label;
+  });
+}
+
+function createReactions(target, id, reactions, userReaction, size) {
+  let container = document.createElement("div");
+  container.classList.add("flex", "gap-2", "justify-end");
+  container.dataset.target = target;
+  container.dataset.id = id;
+  reactionKinds.forEach((kind) => {
+    let button = document.createElement("div");
+    button.classList.add("btn", size);
+    button.dataset.reaction = kind.name;
+    button.title = kind.label;
+    button.textContent = kind.emoji;
+    let count = document.createElement("div");
+    count.classList.add("badge", "badge-secondary");
+    button.appendChild(count);
+    button.addEventListener("click", () => {
+      addReaction(target, id, kind.name, container);
+    });
+    container.appendChild(button);
+  });
+  container.addEventListener("mouseenter", () => {
+    showReactors(target, id, container);
+  });
+  updateReactions(container, reactions, userReaction);
+  return container;
+}
+
+async function getReactions(id) {
   let response = await fetch(`/api/userreaction?post_id=${id}`, {
     credentials: "same-origin",
     method: "GET",
   });
+  return await response.json();
+}
+
+// Must match comments::MAX_DEPTH on the server
+const MAX_COMMENT_DEPTH = 4;
+
+function createReplyForm(postId, commentId) {
+  let form = document.createElement("form");
+  form.setAttribute("action", "/api/comment");
+  form.setAttribute("method", "POST");
+  form.classList.add("hidden", "flex", "gap-2", "mt-2");
+  let postInput = document.createElement("input");
+  postInput.type = "hidden";
+  postInput.setAttribute("name", "post_id");
+  postInput.value = postId;
+  let parentInput = document.createElement("input");
+  parentInput.type = "hidden";
+  parentInput.setAttribute("name", "parent_comment_id");
+  parentInput.value = commentId;
+  let input = document.createElement("input");
+  input.classList.add("input", "input-bordered", "input-sm", "grow");
+  input.setAttribute("name", "content");
+  input.setAttribute("required", "");
+  input.placeholder = "Reply";
+  let button = document.createElement("button");
+  button.classList.add("btn", "btn-sm", "btn-primary");
+  button.textContent = "Reply";
+  form.appendChild(postInput);
+  form.appendChild(parentInput);
+  form.appendChild(input);
+  form.appendChild(button);
+  return form;
+}
+
+function createComment(comment, postId, userReactions) {
+  let commentDiv = document.createElement("div");
+  commentDiv.classList.add("flex", "gap-4");
+  let avatar = document.createElement("div");
+  avatar.classList.add("avatar");
+  let avatarImg = document.createElement("div");
+  avatarImg.classList.add("w-12", "h-12", "rounded-btn");
+  let pfp = document.createElement("img");
+  pfp.src = comment.author.avatar;
+  pfp.alt = "Avatar";
+  avatarImg.appendChild(pfp);
+  avatar.appendChild(avatarImg);
+  let commentContent = document.createElement("div");
+  commentContent.classList.add("flex", "flex-col", "grow");
+  let commentDetails = document.createElement("div");
+  commentDetails.classList.add(
+    "flex",
+    "flex-col",
+    "lg:flex-row",
+    "lg:justify-between",
+    "lg:items-center",
+    "w-ful",
+  );
+  let commentOwner = document.createElement("a");
+  commentOwner.classList.add("font-bold", "link", "link-hover");
+  commentOwner.href = `/u/${comment.author.handle}`;
+  commentOwner.textContent = comment.author.display_name;
+  let commentDate = document.createElement("div");
+  commentDate.classList.add("text-xs", "text-gray-500");
+  commentDate.textContent = comment.datetime;
+  commentDetails.appendChild(commentOwner);
+  commentDetails.appendChild(commentDate);
+  let commentText = document.createElement("div");
+  commentText.classList.add("prose", "prose-sm", "max-w-none", "break-words");
+  commentText.innerHTML = comment.content_html;
+  commentContent.appendChild(commentDetails);
+  commentContent.appendChild(commentText);
+  let commentReactions = createReactions(
+    "comment",
+    comment.comment_id,
+    comment.reactions,
+    userReactions[comment.comment_id],
+    "btn-xs",
+  );
+  commentReactions.classList.remove("justify-end");
+  commentReactions.classList.add("mt-1");
+  commentContent.appendChild(commentReactions);
+
+  if (comment.depth < MAX_COMMENT_DEPTH) {
+    let replyButton = document.createElement("button");
+    replyButton.classList.add("btn", "btn-ghost", "btn-xs", "self-start");
+    replyButton.textContent = "Reply";
+    let replyForm = createReplyForm(postId, comment.comment_id);
+    replyButton.addEventListener("click", () => {
+      replyForm.classList.toggle("hidden");
+    });
+    commentContent.appendChild(replyButton);
+    commentContent.appendChild(replyForm);
+  }
+
+  if (comment.replies.length > 0) {
+    let replies = document.createElement("div");
+    replies.classList.add(
+      "flex",
+      "flex-col",
+      "gap-4",
+      "mt-4",
+      "pl-4",
+      "border-l-2",
+      "border-base-300",
+    );
+    comment.replies.forEach((reply) => {
+      replies.appendChild(createComment(reply, postId, userReactions));
+    });
+    commentContent.appendChild(replies);
+  }
+
+  commentDiv.appendChild(avatar);
+  commentDiv.appendChild(commentContent);
+  return commentDiv;
+}
+
+function renderComments(container, comments, postId, userReactions) {
+  container.replaceChildren();
+  comments.forEach((comment) => {
+    container.appendChild(createComment(comment, postId, userReactions));
+  });
+
+  if (comments.length === 0) {
+    let noComments = document.createElement("div");
+    noComments.classList.add("text-center", "text-gray-500");
+    noComments.textContent = "No comments yet";
+    container.appendChild(noComments);
+  }
+}
+
+function renderBookmarkButton(button, bookmarked) {
+  button.dataset.bookmarked = bookmarked;
+  button.textContent = bookmarked ? "Bookmarked" : "Bookmark";
+  button.classList.toggle("btn-primary", bookmarked);
+  button.classList.toggle("btn-ghost", !bookmarked);
+}
+
+async function toggleBookmark(button) {
+  let bookmarked = button.dataset.bookmarked === "true";
+  let response = await fetch(`/api/posts/${button.dataset.postId}/bookmark`, {
+    credentials: "same-origin",
+    method: bookmarked ? "DELETE" : "POST",
+  });
+  if (!response.ok) {
+    return;
+  }
   let data = await response.json();
-  return data.type;
+  renderBookmarkButton(button, data.bookmarked);
+  // Removing a bookmark from the bookmarks tab removes the post from the list
+  let container = document.getElementById("posts");
+  if (!data.bookmarked && container.dataset.feed === "bookmarks") {
+    document.getElementById(button.dataset.postId)?.remove();
+  }
+}
+
+function createBookmarkButton(content) {
+  let button = document.createElement("button");
+  button.classList.add("btn", "btn-sm");
+  button.dataset.postId = content.post_id;
+  renderBookmarkButton(button, content.bookmarked === true);
+  button.addEventListener("click", () => toggleBookmark(button));
+  return button;
 }
 
 async function createCard(content) {
@@ -60,22 +261,27 @@ async function createCard(content) {
   let avatarImg = document.createElement("div");
   avatarImg.classList.add("w-12", "rounded-btn");
   let pfpOwner = document.createElement("img");
-  pfpOwner.src = `https://api.dicebear.com/8.x/notionists-neutral/svg?seed=${content.email}`;
+  pfpOwner.src = content.author.avatar;
   pfpOwner.alt = "Avatar";
   avatarImg.appendChild(pfpOwner);
   avatar.appendChild(avatarImg);
   let postDetails = document.createElement("div");
   postDetails.classList.add("flex", "flex-col");
-  let ownerEmail = document.createElement("div");
-  ownerEmail.classList.add("font-bold");
-  ownerEmail.textContent = content.email;
+  let ownerName = document.createElement("a");
+  ownerName.classList.add("font-bold", "link", "link-hover");
+  ownerName.href = `/u/${content.author.handle}`;
+  ownerName.textContent = content.author.display_name;
   let postDate = document.createElement("div");
   postDate.classList.add("text-sm", "text-gray-500");
   postDate.textContent = content.datetime;
-  postDetails.appendChild(ownerEmail);
+  postDetails.appendChild(ownerName);
   postDetails.appendChild(postDate);
   owner.appendChild(avatar);
   owner.appendChild(postDetails);
+  let spacer = document.createElement("div");
+  spacer.classList.add("grow");
+  owner.appendChild(spacer);
+  owner.appendChild(createBookmarkButton(content));
 
   let figure = document.createElement("figure");
   if (content.image) {
@@ -90,113 +296,49 @@ async function createCard(content) {
   cardBody.classList.add("card-body");
   let h2 = document.createElement("h2");
   h2.classList.add("card-title");
-  h2.textContent = content.title;
-  let p = document.createElement("p");
-  p.classList.add("break-words", "whitespace-pre-line");
-  p.textContent = decodeURIComponent(content.content);
-  evalLinks(p);
+  let permalink = document.createElement("a");
+  permalink.classList.add("link", "link-hover");
+  permalink.href = `/post/${content.post_id}`;
+  permalink.textContent = content.title;
+  h2.appendChild(permalink);
+  let p = document.createElement("div");
+  p.classList.add("prose", "max-w-none", "break-words");
+  p.innerHTML = content.content_html;
 
-  let userReaction = await getReaction(content.post_id);
+  let tags = document.createElement("div");
+  tags.classList.add("flex", "flex-wrap", "gap-2");
+  content.tags.forEach((tag) => {
+    let tagLink = document.createElement("a");
+    tagLink.classList.add("badge", "badge-outline");
+    tagLink.href = `/tag/${encodeURIComponent(tag)}`;
+    tagLink.textContent = `#${tag}`;
+    tags.appendChild(tagLink);
+  });
 
-  let reactions = document.createElement("div");
+  let userReactions = await getReactions(content.post_id);
+
+  let reactions = createReactions(
+    "post",
+    content.post_id,
+    content.reactions,
+    userReactions.type,
+    "btn-sm",
+  );
   reactions.id = "reactions";
-  reactions.classList.add("flex", "gap-4", "justify-end", "mt-4");
-  let heart = document.createElement("div");
-  heart.classList.add("btn", "btn-sm", "heart");
-  if (userReaction === "heart") {
-    heart.classList.add("btn-outline");
-  }
-  heart.textContent = "\u2764";
-  let heartCount = document.createElement("div");
-  heartCount.textContent = content.reactions.heart || 0;
-  heartCount.classList.add("badge", "badge-secondary");
-  heart.appendChild(heartCount);
-  let thumbsUp = document.createElement("div");
-  thumbsUp.classList.add("btn", "btn-sm", "thumbsUp");
-  if (userReaction === "thumbsUp") {
-    thumbsUp.classList.add("btn-outline");
-  }
-  thumbsUp.textContent = "\u{1F44D}";
-  let thumbsUpCount = document.createElement("div");
-  thumbsUpCount.textContent = content.reactions.thumbsUp || 0;
-  thumbsUpCount.classList.add("badge", "badge-secondary");
-  thumbsUp.appendChild(thumbsUpCount);
-  let thumbsDown = document.createElement("div");
-  thumbsDown.classList.add("btn", "btn-sm", "thumbsDown");
-  if (userReaction === "thumbsDown") {
-    thumbsDown.classList.add("btn-outline");
-  }
-  thumbsDown.textContent = "\u{1F44E}";
-  let thumbsDownCount = document.createElement("div");
-  thumbsDownCount.textContent = content.reactions.thumbsDown || 0;
-  thumbsDownCount.classList.add("badge", "badge-secondary");
-  thumbsDown.appendChild(thumbsDownCount);
-  reactions.appendChild(heart);
-  reactions.appendChild(thumbsUp);
-  reactions.appendChild(thumbsDown);
-
-  heart.addEventListener("click", () => {
-    addReaction("heart", content.post_id);
-  });
-  thumbsUp.addEventListener("click", () => {
-    addReaction("thumbsUp", content.post_id);
-  });
-  thumbsDown.addEventListener("click", () => {
-    addReaction("thumbsDown", content.post_id);
-  });
+  reactions.classList.add("gap-4", "mt-4");
 
   let divider = document.createElement("div");
   divider.classList.add("divider");
   divider.textContent = "Comments";
   let comments = document.createElement("div");
   comments.classList.add("w-full", "flex", "flex-col", "gap-4");
-  content.comments.forEach((comment) => {
-    let commentDiv = document.createElement("div");
-    commentDiv.classList.add("flex", "gap-4");
-    let avatar = document.createElement("div");
-    avatar.classList.add("avatar");
-    let avatarImg = document.createElement("div");
-    avatarImg.classList.add("w-12", "rounded-btn");
-    let pfp = document.createElement("img");
-    pfp.src = `https://api.dicebear.com/8.x/notionists-neutral/svg?seed=${comment.email}`;
-    pfp.alt = "Avatar";
-    avatarImg.appendChild(pfp);
-    avatar.appendChild(avatarImg);
-    let commentContent = document.createElement("div");
-    commentContent.classList.add("flex", "flex-col", "grow");
-    let commentDetails = document.createElement("div");
-    commentDetails.classList.add(
-      "flex",
-      "flex-col",
-      "lg:flex-row",
-      "lg:justify-between",
-      "lg:items-center",
-      "w-ful",
-    );
-    let commentOwner = document.createElement("div");
-    commentOwner.classList.add("font-bold");
-    commentOwner.textContent = comment.email;
-    let commentDate = document.createElement("div");
-    commentDate.classList.add("text-xs", "text-gray-500");
-    commentDate.textContent = comment.datetime;
-    commentDetails.appendChild(commentOwner);
-    commentDetails.appendChild(commentDate);
-    let commentText = document.createElement("div");
-    commentText.classList.add("break-words", "whitespace-pre-line");
-    commentText.textContent = decodeURIComponent(comment.content);
-    commentContent.appendChild(commentDetails);
-    commentContent.appendChild(commentText);
-    commentDiv.appendChild(avatar);
-    commentDiv.appendChild(commentContent);
-    comments.appendChild(commentDiv);
-  });
-
-  if (content.comments.length === 0) {
-    let noComments = document.createElement("div");
-    noComments.classList.add("text-center", "text-gray-500");
-    noComments.textContent = "No comments yet";
-    comments.appendChild(noComments);
-  }
+  comments.dataset.comments = content.post_id;
+  renderComments(
+    comments,
+    content.comments,
+    content.post_id,
+    userReactions.comments,
+  );
 
   let form = document.createElement("form");
   form.setAttribute("action", "/api/comment");
@@ -223,6 +365,9 @@ async function createCard(content) {
     card.appendChild(figure);
   }
   cardBody.appendChild(h2);
+  if (content.tags.length > 0) {
+    cardBody.appendChild(tags);
+  }
   cardBody.appendChild(p);
   cardBody.appendChild(reactions);
   cardBody.appendChild(divider);
@@ -233,12 +378,366 @@ async function createCard(content) {
   return card;
 }
 
+async function loadTags() {
+  let container = document.getElementById("tags");
+  if (!container) {
+    return;
+  }
+  let response = await fetch("/api/tags");
+  let tags = await response.json();
+  tags.forEach((tag) => {
+    let tagLink = document.createElement("a");
+    tagLink.classList.add("badge", "badge-primary", "badge-lg", "gap-2");
+    tagLink.href = `/tag/${encodeURIComponent(tag.name)}`;
+    tagLink.textContent = `#${tag.name}`;
+    let tagCount = document.createElement("span");
+    tagCount.classList.add("opacity-70");
+    tagCount.textContent = tag.count;
+    tagLink.appendChild(tagCount);
+    container.appendChild(tagLink);
+  });
+}
+
+async function publishDraft(id) {
+  let response = await fetch("/api/publish", {
+    credentials: "same-origin",
+    method: "POST",
+    body: `post_id=${id}&status=published`,
+  });
+  if (response.ok) {
+    location.reload();
+  }
+}
+
+async function showDrafts() {
+  let container = document.getElementById("drafts");
+  container.replaceChildren();
+  let response = await fetch("/api/drafts", { credentials: "same-origin" });
+  let drafts = await response.json();
+  drafts.forEach((draft) => {
+    let row = document.createElement("div");
+    row.classList.add("flex", "gap-4", "items-center", "justify-between");
+    let details = document.createElement("div");
+    details.classList.add("flex", "flex-col");
+    let title = document.createElement("div");
+    title.classList.add("font-bold");
+    title.textContent = draft.title;
+    let status = document.createElement("div");
+    status.classList.add("text-sm", "text-gray-500");
+    status.textContent =
+      draft.status === "scheduled"
+        ? `Scheduled for ${new Date(draft.publish_at).toLocaleString()}`
+        : "Draft";
+    details.appendChild(title);
+    details.appendChild(status);
+    let publish = document.createElement("button");
+    publish.classList.add("btn", "btn-sm", "btn-primary");
+    publish.textContent = "Publish now";
+    publish.addEventListener("click", () => publishDraft(draft.post_id));
+    row.appendChild(details);
+    row.appendChild(publish);
+    container.appendChild(row);
+  });
+  if (drafts.length === 0) {
+    let noDrafts = document.createElement("div");
+    noDrafts.classList.add("text-center", "text-gray-500");
+    noDrafts.textContent = "No drafts";
+    container.appendChild(noDrafts);
+  }
+  drafts_modal.showModal();
+}
+
+// Live updates of posts, comments and reactions pushed by the server
+function connectEvents(container, tag, retryDelay = 1000) {
+  let protocol = location.protocol === "https:" ? "wss" : "ws";
+  let socket = new WebSocket(`${protocol}://${location.host}/api/ws`);
+  socket.addEventListener("open", () => {
+    retryDelay = 1000;
+  });
+  socket.addEventListener("message", (message) => {
+    let event = JSON.parse(message.data);
+    handleEvent(container, tag, event.event, event.data);
+  });
+  socket.addEventListener("close", () => {
+    setTimeout(
+      () => connectEvents(container, tag, Math.min(retryDelay * 2, 30000)),
+      retryDelay,
+    );
+  });
+}
+
+async function handleEvent(container, tag, name, data) {
+  if (name === "post") {
+    if (document.getElementById(data.post_id)) {
+      return;
+    }
+    if (tag && !data.tags.includes(tag)) {
+      return;
+    }
+    // The following and bookmarks feeds are paginated, new posts show up when they are reloaded
+    if (container.dataset.feed !== "global") {
+      return;
+    }
+    container.prepend(await createCard(data));
+  } else if (name === "comment") {
+    let comments = document.querySelector(`[data-comments="${data.post_id}"]`);
+    if (!comments) {
+      return;
+    }
+    let userReactions = await getReactions(data.post_id);
+    renderComments(
+      comments,
+      data.comments,
+      data.post_id,
+      userReactions.comments,
+    );
+  } else if (name === "reaction") {
+    document
+      .querySelectorAll(`[data-target="${data.target}"][data-id="${data.id}"]`)
+      .forEach((reactions) => {
+        updateReactionCounts(reactions, data.reactions);
+        reactions.dataset.reactorsLoaded = "";
+      });
+  }
+}
+
+function updateNotificationsCount(unread) {
+  let count = document.getElementById("notifications-count");
+  if (!count) {
+    return;
+  }
+  count.textContent = unread;
+  count.classList.toggle("hidden", unread === 0);
+}
+
+async function loadNotifications() {
+  if (!document.getElementById("notifications")) {
+    return null;
+  }
+  let response = await fetch("/api/notifications", {
+    credentials: "same-origin",
+  });
+  if (!response.ok) {
+    return null;
+  }
+  let data = await response.json();
+  updateNotificationsCount(data.unread);
+  return data;
+}
+
+async function markNotificationsRead(notificationId) {
+  // keepalive lets the request finish when a notification link navigates away
+  let response = await fetch("/api/notifications/read", {
+    credentials: "same-origin",
+    method: "POST",
+    keepalive: true,
+    body: notificationId ? `notification_id=${notificationId}` : "",
+  });
+  let data = await response.json();
+  updateNotificationsCount(data.unread);
+  if (!notificationId) {
+    document
+      .querySelectorAll("#notifications .font-bold")
+      .forEach((message) => message.classList.remove("font-bold"));
+  }
+}
+
+async function showNotifications() {
+  let data = await loadNotifications();
+  if (!data) {
+    return;
+  }
+  let container = document.getElementById("notifications");
+  container.replaceChildren();
+  data.notifications.forEach((notification) => {
+    let row = document.createElement("a");
+    row.classList.add("flex", "flex-col", "hover:bg-base-200", "rounded-btn", "p-2");
+    row.href = `/post/${notification.post_id}`;
+    let message = document.createElement("div");
+    message.textContent = notification.message;
+    if (!notification.read) {
+      message.classList.add("font-bold");
+    }
+    let datetime = document.createElement("div");
+    datetime.classList.add("text-sm", "text-gray-500");
+    datetime.textContent = new Date(notification.datetime).toLocaleString();
+    row.appendChild(message);
+    row.appendChild(datetime);
+    row.addEventListener("click", () => {
+      if (!notification.read) {
+        markNotificationsRead(notification.notification_id);
+      }
+    });
+    container.appendChild(row);
+  });
+  if (data.notifications.length === 0) {
+    let noNotifications = document.createElement("div");
+    noNotifications.classList.add("text-center", "text-gray-500");
+    noNotifications.textContent = "No notifications";
+    container.appendChild(noNotifications);
+  }
+
+  let digest = document.getElementById("notifications-digest");
+  digest.checked = data.digest;
+  digest.onchange = () => {
+    fetch("/api/notifications/digest", {
+      credentials: "same-origin",
+      method: "POST",
+      body: `enabled=${digest.checked}`,
+    });
+  };
+  notifications_modal.showModal();
+}
+
+async function showProfile() {
+  let response = await fetch("/api/users/me", { credentials: "same-origin" });
+  if (!response.ok) {
+    return;
+  }
+  let profile = await response.json();
+  let form = document.getElementById("profile-form");
+  form.handle.value = profile.handle;
+  form.display_name.value = profile.display_name;
+  form.bio.value = profile.bio;
+  form.website.value = profile.website;
+  form.avatar.value = "";
+  document.getElementById("profile-avatar").src = profile.avatar;
+  document.getElementById("profile-link").href = `/u/${profile.handle}`;
+  document
+    .getElementById("profile-remove-avatar")
+    .classList.toggle("hidden", !profile.uploaded_avatar);
+  document.getElementById("profile-error").textContent = "";
+  profile_modal.showModal();
+}
+
+async function removeAvatar() {
+  let response = await fetch("/api/users/me", {
+    credentials: "same-origin",
+    method: "PATCH",
+    body: JSON.stringify({ avatar: null }),
+  });
+  if (response.ok) {
+    location.reload();
+  }
+}
+
+function setupProfileForm() {
+  let form = document.getElementById("profile-form");
+  if (!form) {
+    return;
+  }
+  form.addEventListener("submit", async (event) => {
+    event.preventDefault();
+    let error = document.getElementById("profile-error");
+    let response = await fetch("/api/users/me", {
+      credentials: "same-origin",
+      method: "PATCH",
+      body: JSON.stringify({
+        handle: form.handle.value,
+        display_name: form.display_name.value,
+        bio: form.bio.value,
+        website: form.website.value,
+      }),
+    });
+    if (response.ok && form.avatar.files.length > 0) {
+      let avatar = new FormData();
+      avatar.append("avatar", form.avatar.files[0]);
+      response = await fetch("/api/users/me/avatar", {
+        credentials: "same-origin",
+        method: "POST",
+        body: avatar,
+      });
+    }
+    if (!response.ok) {
+      error.textContent = await response.text();
+      return;
+    }
+    location.reload();
+  });
+}
+
+function setupUploadForm() {
+  let form = document.getElementById("upload-form");
+  if (!form) {
+    return;
+  }
+  let status = document.getElementById("upload-status");
+  let localPublishAt = document.getElementById("upload-publish-at-local");
+  let publishAt = document.getElementById("upload-publish-at");
+  status.addEventListener("change", () => {
+    localPublishAt.classList.toggle("hidden", status.value !== "scheduled");
+    localPublishAt.required = status.value === "scheduled";
+  });
+  // The server works in UTC, the datetime-local input is in the browser's timezone
+  form.addEventListener("submit", () => {
+    publishAt.value = localPublishAt.value
+      ? new Date(localPublishAt.value).toISOString()
+      : "";
+  });
+}
+
+// Global posts (optionally of a tag) or the paginated posts of the followed authors or bookmarks
+async function loadPosts(container, tag, page = 1) {
+  let loadMore = document.getElementById("load-more");
+  let posts;
+  let hasMore = false;
+  if (container.dataset.feed !== "global") {
+    let endpoint = container.dataset.feed === "following" ? "feed" : "bookmarks";
+    let response = await fetch(`/api/${endpoint}?page=${page}`, {
+      credentials: "same-origin",
+    });
+    let data = await response.json();
+    posts = data.posts;
+    hasMore = data.has_more;
+  } else {
+    let response = await fetch(
+      tag ? `/api/posts?tag=${encodeURIComponent(tag)}` : "/api/posts",
+    );
+    posts = await response.json();
+  }
+
+  if (page === 1) {
+    container.replaceChildren();
+  }
+  for (let post of posts) {
+    container.appendChild(await createCard(post));
+  }
+  if (page === 1 && posts.length === 0 && container.dataset.feed !== "global") {
+    let noPosts = document.createElement("div");
+    noPosts.classList.add("text-center", "text-gray-500");
+    noPosts.textContent =
+      container.dataset.feed === "following"
+        ? "Follow some authors to see their posts here"
+        : "Bookmark some posts to find them here";
+    container.appendChild(noPosts);
+  }
+  if (loadMore) {
+    loadMore.classList.toggle("hidden", !hasMore);
+    loadMore.onclick = () => loadPosts(container, tag, page + 1);
+  }
+}
+
+function setupFeedTabs(container, tag) {
+  document.querySelectorAll("#feed-tabs [data-feed]").forEach((tab) => {
+    tab.addEventListener("click", () => {
+      document
+        .querySelectorAll("#feed-tabs [data-feed]")
+        .forEach((other) => other.classList.toggle("tab-active", other === tab));
+      container.dataset.feed = tab.dataset.feed;
+      loadPosts(container, tag);
+    });
+  });
+}
+
 document.addEventListener("DOMContentLoaded", async () => {
-  let response = await fetch("/api/posts");
-  let posts = await response.json();
+  setupUploadForm();
+  setupProfileForm();
+  loadTags();
+  loadNotifications();
+  await loadReactionKinds();
   let container = document.getElementById("posts");
-  posts.forEach(async (post) => {
-    let card = await createCard(post);
-    container.appendChild(card);
-  });
+  let tag = container.dataset.tag;
+  setupFeedTabs(container, tag);
+  await loadPosts(container, tag);
+  connectEvents(container, tag);
 });
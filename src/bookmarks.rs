@@ -0,0 +1,56 @@
+use rusqlite::Connection;
+
+use crate::pagination;
+
+// Returns false when the post was already bookmarked
+pub fn add(dbconn: &Connection, email: &str, post_id: i64) -> bool {
+    let inserted: usize = dbconn
+        .execute(
+            "INSERT OR IGNORE INTO bookmarks (email, post_id) VALUES (?1, ?2)",
+            [
+                &email as &dyn rusqlite::ToSql,
+                &post_id as &dyn rusqlite::ToSql,
+            ],
+        )
+        .unwrap();
+    return inserted > 0;
+}
+
+// Returns false when the post wasn't bookmarked
+pub fn remove(dbconn: &Connection, email: &str, post_id: i64) -> bool {
+    let deleted: usize = dbconn
+        .execute(
+            "DELETE FROM bookmarks WHERE email = ?1 AND post_id = ?2",
+            [
+                &email as &dyn rusqlite::ToSql,
+                &post_id as &dyn rusqlite::ToSql,
+            ],
+        )
+        .unwrap();
+    return deleted > 0;
+}
+
+pub fn is_bookmarked(dbconn: &Connection, email: &str, post_id: i64) -> bool {
+    return dbconn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM bookmarks WHERE email = ?1 AND post_id = ?2)",
+            [
+                &email as &dyn rusqlite::ToSql,
+                &post_id as &dyn rusqlite::ToSql,
+            ],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+}
+
+// A page of the published posts bookmarked by the user, most recently bookmarked first
+pub fn list(dbconn: &Connection, email: &str, page: usize) -> (Vec<i64>, bool) {
+    return pagination::post_ids(
+        dbconn,
+        "SELECT bookmarks.post_id FROM bookmarks JOIN posts ON posts.post_id = bookmarks.post_id
+            WHERE bookmarks.email = ?1 AND posts.status = 'published' AND posts.publish_at <= CURRENT_TIMESTAMP
+            ORDER BY bookmarks.datetime DESC, bookmarks.rowid DESC LIMIT ?2 OFFSET ?3",
+        email,
+        page,
+    );
+}
@@ -0,0 +1,122 @@
+use json::JsonValue;
+use rusqlite::Connection;
+
+use crate::profiles;
+use crate::reactions::{self, Target};
+
+// Top level comments have depth 0, a reply is one level deeper than its parent
+pub const MAX_DEPTH: usize = 4;
+
+struct Comment {
+    comment_id: i64,
+    parent_comment_id: Option<i64>,
+    author: JsonValue,
+    content: String,
+    content_html: String,
+    datetime: String,
+    reactions: JsonValue,
+}
+
+// Published posts can be commented by everyone, drafts and scheduled posts only by their author
+pub fn can_comment(dbconn: &Connection, post_id: i64, email: &str) -> bool {
+    return dbconn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM posts WHERE post_id = ?1
+                AND ((status = 'published' AND publish_at <= CURRENT_TIMESTAMP) OR email = ?2))",
+            [
+                &post_id as &dyn rusqlite::ToSql,
+                &email as &dyn rusqlite::ToSql,
+            ],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+}
+
+// Depth of a comment of the given post, None if the comment does not belong to the post
+pub fn depth(dbconn: &Connection, post_id: i64, comment_id: i64) -> Option<usize> {
+    let mut depth: usize = 0;
+    let mut parent: Option<i64> = dbconn
+        .query_row(
+            "SELECT parent_comment_id FROM comments WHERE comment_id = ?1 AND post_id = ?2",
+            [comment_id, post_id],
+            |row| row.get(0),
+        )
+        .ok()?;
+
+    while let Some(parent_comment_id) = parent {
+        depth += 1;
+        if depth > MAX_DEPTH {
+            break;
+        }
+        parent = dbconn
+            .query_row(
+                "SELECT parent_comment_id FROM comments WHERE comment_id = ?1",
+                [parent_comment_id],
+                |row| row.get(0),
+            )
+            .ok()?;
+    }
+    return Some(depth);
+}
+
+fn build(comments: &[Comment], parent: Option<i64>, depth: usize) -> JsonValue {
+    let mut replies: JsonValue = JsonValue::new_array();
+    for comment in comments
+        .iter()
+        .filter(|comment: &&Comment| comment.parent_comment_id == parent)
+    {
+        replies
+            .push(json::object! {
+                comment_id: comment.comment_id,
+                parent_comment_id: comment.parent_comment_id,
+                depth: depth,
+                author: comment.author.clone(),
+                content: comment.content.clone(),
+                content_html: comment.content_html.clone(),
+                datetime: comment.datetime.clone() + " UTC",
+                reactions: comment.reactions.clone(),
+                replies: build(comments, Some(comment.comment_id), depth + 1)
+            })
+            .unwrap();
+    }
+    return replies;
+}
+
+// Comments of a post as a tree: newest threads first, replies in chronological order.
+// The emails of the authors are only included for admins.
+pub fn tree(dbconn: &Connection, post_id: i64, show_emails: bool) -> JsonValue {
+    let mut stmt = dbconn
+        .prepare(
+            "SELECT comment_id, parent_comment_id, email, content, content_html, datetime FROM comments
+                WHERE post_id = ? ORDER BY datetime ASC, comment_id ASC",
+        )
+        .unwrap();
+    let comments_iter = stmt.query_map([post_id], |row| {
+        Ok((
+            Comment {
+                comment_id: row.get::<_, i64>(0)?,
+                parent_comment_id: row.get::<_, Option<i64>>(1)?,
+                author: JsonValue::Null,
+                content: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                content_html: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                datetime: row.get::<_, String>(5)?,
+                reactions: JsonValue::new_object(),
+            },
+            row.get::<_, String>(2)?,
+        ))
+    });
+    let mut comments: Vec<Comment> = Vec::new();
+    for comment in comments_iter.unwrap() {
+        let (mut comment, email): (Comment, String) = comment.unwrap();
+        comment.author = profiles::author_json(dbconn, &email, show_emails);
+        comment.reactions = reactions::counts(dbconn, &Target::Comment(comment.comment_id));
+        comments.push(comment);
+    }
+
+    let threads: JsonValue = build(&comments, None, 0);
+    let mut newest_first: JsonValue = JsonValue::new_array();
+    for thread in threads.members().rev() {
+        newest_first.push(thread.clone()).unwrap();
+    }
+    return newest_first;
+}
@@ -0,0 +1,97 @@
+use std::env;
+
+use crate::http::request::find_header;
+
+fn var(key: &str) -> Option<String> {
+    return env::var(key)
+        .ok()
+        .filter(|value: &String| !value.trim().is_empty());
+}
+
+// http://host[:port]/path that receives a JSON POST every time a post gets published
+pub fn publish_webhook() -> Option<String> {
+    return var("PUBLISH_WEBHOOK");
+}
+
+// Absolute URL of the blog used in feeds and permalinks, falls back to the Host header
+pub fn public_url(headers: &[(String, String)]) -> String {
+    if let Some(url) = var("PUBLIC_URL") {
+        return url.trim_end_matches('/').to_string();
+    }
+
+    let host: &str = find_header(headers, "Host").unwrap_or("localhost");
+    return format!("http://{}", host);
+}
+
+// Comma separated name:emoji:label entries, e.g. "heart:❤:Love,thumbsUp:👍:Like"
+pub fn reactions() -> Option<String> {
+    return var("REACTIONS");
+}
+
+// "log" (default), "sendmail", "sendmail:<command>" or "smtp://host:port"
+pub fn mail_transport() -> Option<String> {
+    return var("MAIL_TRANSPORT");
+}
+
+pub fn mail_from() -> String {
+    return var("MAIL_FROM").unwrap_or("blog@localhost".to_string());
+}
+
+// Seconds between two notification digests, one day by default
+pub fn digest_interval() -> u64 {
+    return var("DIGEST_INTERVAL")
+        .and_then(|interval: String| interval.trim().parse::<u64>().ok())
+        .filter(|interval: &u64| *interval > 0)
+        .unwrap_or(86400);
+}
+
+// Comma separated emails of the users that can see the emails of everyone else
+pub fn admin_emails() -> Vec<String> {
+    return var("ADMIN_EMAILS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|email: &str| email.trim().to_lowercase())
+        .filter(|email: &String| !email.is_empty())
+        .collect();
+}
+
+// Directory of the static files and uploaded images, "public" by default
+pub fn web_root() -> String {
+    return var("WEB_ROOT")
+        .map(|root: String| root.trim_end_matches('/').to_string())
+        .unwrap_or("public".to_string());
+}
+
+// Writable directory of the uploaded images served under /images/, inside the web root by default
+pub fn upload_dir() -> String {
+    return var("UPLOAD_DIR")
+        .map(|dir: String| dir.trim_end_matches('/').to_string())
+        .unwrap_or(format!("{}/images", web_root()));
+}
+
+// Reads the static files from the web root before the embedded ones, so they can be edited
+// without rebuilding. Only makes a difference when built with the embed feature.
+pub fn dev_assets() -> bool {
+    return var("DEV_ASSETS").is_some_and(|dev: String| {
+        ["1", "true", "on"].contains(&dev.trim().to_lowercase().as_str())
+    });
+}
+
+// Cache-Control by path prefix as "prefix=policy" rules separated by ";",
+// e.g. "/images/=public, max-age=86400;/api/=private, no-cache"
+pub fn cache_control() -> Vec<(String, String)> {
+    return var("CACHE_CONTROL")
+        .unwrap_or("/=no-cache;/images/=public, max-age=86400;/api/=private, no-cache".to_string())
+        .split(';')
+        .filter_map(|rule: &str| rule.split_once('='))
+        .map(|(prefix, policy)| (prefix.trim().to_string(), policy.trim().to_string()))
+        .filter(|(prefix, policy)| !prefix.is_empty() && !policy.is_empty())
+        .collect();
+}
+
+// Lists the folders of the web root without an index.html, off by default
+pub fn autoindex() -> bool {
+    return var("AUTOINDEX").is_some_and(|autoindex: String| {
+        ["1", "true", "on"].contains(&autoindex.trim().to_lowercase().as_str())
+    });
+}
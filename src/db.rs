@@ -1,3 +1,5 @@
+use crate::markdown;
+use crate::profiles;
 use rusqlite::Connection;
 
 pub fn dbconn() -> Connection {
@@ -22,7 +24,10 @@ pub fn init_db() {
                     email TEXT NOT NULL,
                     title TEXT NOT NULL,
                     content TEXT,
+                    content_html TEXT,
                     image VARCHAR(255),
+                    status TEXT NOT NULL DEFAULT 'published',
+                    publish_at DATETIME,
                     datetime DEFAULT CURRENT_TIMESTAMP,
                     FOREIGN KEY(email) REFERENCES users(email) ON DELETE CASCADE
                 );",
@@ -35,10 +40,13 @@ pub fn init_db() {
             "CREATE TABLE IF NOT EXISTS comments (
                     comment_id INTEGER PRIMARY KEY AUTOINCREMENT,
                     post_id INTEGER NOT NULL,
+                    parent_comment_id INTEGER,
                     email TEXT NOT NULL,
                     content TEXT,
+                    content_html TEXT,
                     datetime DEFAULT CURRENT_TIMESTAMP,
                     FOREIGN KEY(post_id) REFERENCES posts(post_id) ON DELETE CASCADE,
+                    FOREIGN KEY(parent_comment_id) REFERENCES comments(comment_id) ON DELETE CASCADE,
                     FOREIGN KEY(email) REFERENCES users(email) ON DELETE CASCADE
                 );",
             [],
@@ -60,6 +68,22 @@ pub fn init_db() {
         )
         .unwrap();
 
+    dbconn()
+        .execute(
+            "CREATE TABLE IF NOT EXISTS comment_reactions (
+                reaction_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                comment_id INTEGER NOT NULL,
+                email TEXT NOT NULL,
+                type VARCHAR(255) NOT NULL,
+                datetime DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE(comment_id, email),
+                FOREIGN KEY(comment_id) REFERENCES comments(comment_id) ON DELETE CASCADE,
+                FOREIGN KEY(email) REFERENCES users(email) ON DELETE CASCADE
+            )",
+            [],
+        )
+        .unwrap();
+
     dbconn()
         .execute(
             "CREATE TABLE IF NOT EXISTS tokens (
@@ -71,4 +95,222 @@ pub fn init_db() {
             [],
         )
         .unwrap();
+
+    dbconn()
+        .execute(
+            "CREATE TABLE IF NOT EXISTS tags (
+                    tag_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL UNIQUE
+                );",
+            [],
+        )
+        .unwrap();
+
+    dbconn()
+        .execute(
+            "CREATE TABLE IF NOT EXISTS post_tags (
+                    post_id INTEGER NOT NULL,
+                    tag_id INTEGER NOT NULL,
+                    PRIMARY KEY(post_id, tag_id),
+                    FOREIGN KEY(post_id) REFERENCES posts(post_id) ON DELETE CASCADE,
+                    FOREIGN KEY(tag_id) REFERENCES tags(tag_id) ON DELETE CASCADE
+                );",
+            [],
+        )
+        .unwrap();
+
+    dbconn()
+        .execute(
+            "CREATE TABLE IF NOT EXISTS profiles (
+                    user_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    email TEXT NOT NULL UNIQUE,
+                    handle TEXT NOT NULL UNIQUE,
+                    display_name TEXT,
+                    bio TEXT,
+                    website TEXT,
+                    avatar TEXT,
+                    FOREIGN KEY(email) REFERENCES users(email) ON DELETE CASCADE
+                );",
+            [],
+        )
+        .unwrap();
+
+    dbconn()
+        .execute(
+            "CREATE TABLE IF NOT EXISTS follows (
+                    follower TEXT NOT NULL,
+                    followee TEXT NOT NULL,
+                    datetime DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY(follower, followee),
+                    FOREIGN KEY(follower) REFERENCES users(email) ON DELETE CASCADE,
+                    FOREIGN KEY(followee) REFERENCES users(email) ON DELETE CASCADE
+                );",
+            [],
+        )
+        .unwrap();
+
+    dbconn()
+        .execute(
+            "CREATE TABLE IF NOT EXISTS bookmarks (
+                    email TEXT NOT NULL,
+                    post_id INTEGER NOT NULL,
+                    datetime DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY(email, post_id),
+                    FOREIGN KEY(email) REFERENCES users(email) ON DELETE CASCADE,
+                    FOREIGN KEY(post_id) REFERENCES posts(post_id) ON DELETE CASCADE
+                );",
+            [],
+        )
+        .unwrap();
+
+    dbconn()
+        .execute(
+            "CREATE TABLE IF NOT EXISTS mentions (
+                    mention_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    email TEXT NOT NULL,
+                    post_id INTEGER NOT NULL,
+                    comment_id INTEGER,
+                    datetime DEFAULT CURRENT_TIMESTAMP,
+                    FOREIGN KEY(email) REFERENCES users(email) ON DELETE CASCADE,
+                    FOREIGN KEY(post_id) REFERENCES posts(post_id) ON DELETE CASCADE,
+                    FOREIGN KEY(comment_id) REFERENCES comments(comment_id) ON DELETE CASCADE
+                );",
+            [],
+        )
+        .unwrap();
+
+    dbconn()
+        .execute(
+            "CREATE TABLE IF NOT EXISTS notifications (
+                    notification_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    email TEXT NOT NULL,
+                    actor TEXT NOT NULL,
+                    kind TEXT NOT NULL,
+                    post_id INTEGER NOT NULL,
+                    comment_id INTEGER,
+                    detail TEXT NOT NULL DEFAULT '',
+                    read INTEGER NOT NULL DEFAULT 0,
+                    emailed INTEGER NOT NULL DEFAULT 0,
+                    datetime DEFAULT CURRENT_TIMESTAMP,
+                    FOREIGN KEY(email) REFERENCES users(email) ON DELETE CASCADE,
+                    FOREIGN KEY(post_id) REFERENCES posts(post_id) ON DELETE CASCADE,
+                    FOREIGN KEY(comment_id) REFERENCES comments(comment_id) ON DELETE CASCADE
+                );",
+            [],
+        )
+        .unwrap();
+
+    migrate_db();
+}
+
+fn add_column(dbconn: &Connection, table: &str, column: &str, definition: &str) -> bool {
+    let exists: bool = dbconn
+        .query_row(
+            &format!(
+                "SELECT EXISTS(SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1)",
+                table
+            ),
+            [column],
+            |row| row.get(0),
+        )
+        .unwrap();
+    if exists {
+        return false;
+    }
+
+    dbconn
+        .execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )
+        .unwrap();
+    return true;
+}
+
+// Content used to be stored urlencoded, keep the Markdown source and its rendered HTML instead
+fn migrate_markdown(dbconn: &Connection, table: &str, id_column: &str) {
+    if !add_column(dbconn, table, "content_html", "TEXT") {
+        return;
+    }
+
+    let mut stmt = dbconn
+        .prepare(&format!("SELECT {}, content FROM {}", id_column, table))
+        .unwrap();
+    let rows: Vec<(i64, String)> = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            ))
+        })
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+
+    for (id, content) in rows {
+        let source: String = urlencoding::decode(&content)
+            .map(|decoded| decoded.to_string())
+            .unwrap_or(content);
+        dbconn
+            .execute(
+                &format!(
+                    "UPDATE {} SET content = ?1, content_html = ?2 WHERE {} = ?3",
+                    table, id_column
+                ),
+                [
+                    &source as &dyn rusqlite::ToSql,
+                    &markdown::render(&source) as &dyn rusqlite::ToSql,
+                    &id as &dyn rusqlite::ToSql,
+                ],
+            )
+            .unwrap();
+    }
+}
+
+// Posts created before drafts existed are all published at their creation time
+fn migrate_status(dbconn: &Connection) {
+    add_column(
+        dbconn,
+        "posts",
+        "status",
+        "TEXT NOT NULL DEFAULT 'published'",
+    );
+    if add_column(dbconn, "posts", "publish_at", "DATETIME") {
+        dbconn
+            .execute(
+                "UPDATE posts SET publish_at = datetime WHERE status = 'published'",
+                [],
+            )
+            .unwrap();
+    }
+}
+
+// Users created before profiles existed get one with the default handle
+fn migrate_profiles(dbconn: &Connection) {
+    let mut stmt = dbconn
+        .prepare("SELECT email FROM users WHERE email NOT IN (SELECT email FROM profiles)")
+        .unwrap();
+    let emails: Vec<String> = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .unwrap()
+        .map(|email| email.unwrap())
+        .collect();
+    for email in emails {
+        profiles::ensure(dbconn, &email);
+    }
+}
+
+fn migrate_db() {
+    let dbconn: Connection = dbconn();
+    migrate_markdown(&dbconn, "posts", "post_id");
+    migrate_markdown(&dbconn, "comments", "comment_id");
+    migrate_status(&dbconn);
+    add_column(
+        &dbconn,
+        "comments",
+        "parent_comment_id",
+        "INTEGER REFERENCES comments(comment_id) ON DELETE CASCADE",
+    );
+    add_column(&dbconn, "users", "digest", "INTEGER NOT NULL DEFAULT 0");
+    migrate_profiles(&dbconn);
 }
@@ -0,0 +1,92 @@
+use json::JsonValue;
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+// Events a slow client can fall behind before it starts missing them
+const CHANNEL_CAPACITY: usize = 256;
+// Latest events kept to resume Server-Sent Events streams (Last-Event-ID)
+const BACKLOG_SIZE: usize = 100;
+
+pub const POST: &str = "post";
+pub const COMMENT: &str = "comment";
+pub const REACTION: &str = "reaction";
+
+#[derive(Clone)]
+pub struct Event {
+    pub id: u64,
+    pub name: String,
+    pub data: String,
+}
+
+struct Bus {
+    sender: broadcast::Sender<Event>,
+    backlog: VecDeque<Event>,
+    next_id: u64,
+}
+
+impl Event {
+    // {"id": id, "event": name, "data": {...}}, the format of the WebSocket messages
+    pub fn to_json(&self) -> String {
+        return format!(
+            r#"{{"id":{},"event":"{}","data":{}}}"#,
+            self.id, self.name, self.data
+        );
+    }
+}
+
+fn bus() -> &'static Mutex<Bus> {
+    static BUS: OnceLock<Mutex<Bus>> = OnceLock::new();
+    return BUS.get_or_init(|| {
+        // Ids start from the startup time so they keep growing across restarts
+        let start: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        Mutex::new(Bus {
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+            backlog: VecDeque::with_capacity(BACKLOG_SIZE),
+            next_id: start,
+        })
+    });
+}
+
+// Sends the event to every connected client
+pub fn emit(name: &str, data: JsonValue) {
+    let mut bus = bus().lock().unwrap();
+    bus.next_id += 1;
+    let event: Event = Event {
+        id: bus.next_id,
+        name: name.to_string(),
+        data: data.dump(),
+    };
+    if bus.backlog.len() == BACKLOG_SIZE {
+        bus.backlog.pop_front();
+    }
+    bus.backlog.push_back(event.clone());
+    // Sending only fails when nobody is listening, which is fine
+    let _ = bus.sender.send(event);
+}
+
+pub fn subscribe() -> broadcast::Receiver<Event> {
+    return bus().lock().unwrap().sender.subscribe();
+}
+
+// Subscribes and returns the events after last_id that are still in the backlog.
+// The flag is false when older events were already dropped and the client missed some of them.
+pub fn resume(last_id: u64) -> (Vec<Event>, bool, broadcast::Receiver<Event>) {
+    // Holding the lock while subscribing means no event is missed or sent twice
+    let bus = bus().lock().unwrap();
+    let missed: Vec<Event> = bus
+        .backlog
+        .iter()
+        .filter(|event: &&Event| event.id > last_id)
+        .cloned()
+        .collect();
+    let complete: bool = match bus.backlog.front() {
+        Some(oldest) => oldest.id <= last_id.saturating_add(1),
+        None => true,
+    };
+    return (missed, complete, bus.sender.subscribe());
+}
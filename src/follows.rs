@@ -0,0 +1,66 @@
+use json::JsonValue;
+use rusqlite::Connection;
+
+use crate::pagination;
+
+// Returns false when the user was already following the author
+pub fn follow(dbconn: &Connection, follower: &str, followee: &str) -> bool {
+    let inserted: usize = dbconn
+        .execute(
+            "INSERT OR IGNORE INTO follows (follower, followee) VALUES (?1, ?2)",
+            [follower, followee],
+        )
+        .unwrap();
+    return inserted > 0;
+}
+
+// Returns false when the user wasn't following the author
+pub fn unfollow(dbconn: &Connection, follower: &str, followee: &str) -> bool {
+    let deleted: usize = dbconn
+        .execute(
+            "DELETE FROM follows WHERE follower = ?1 AND followee = ?2",
+            [follower, followee],
+        )
+        .unwrap();
+    return deleted > 0;
+}
+
+pub fn is_following(dbconn: &Connection, follower: &str, followee: &str) -> bool {
+    return dbconn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM follows WHERE follower = ?1 AND followee = ?2)",
+            [follower, followee],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+}
+
+// {"followers": ..., "following": ...} of the user
+pub fn counts(dbconn: &Connection, email: &str) -> JsonValue {
+    let count = |column: &str| -> i64 {
+        return dbconn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM follows WHERE {} = ?1", column),
+                [email],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+    };
+    return json::object! {
+        followers: count("followee"),
+        following: count("follower")
+    };
+}
+
+// A page of the published posts of the authors followed by the user, newest first
+pub fn feed(dbconn: &Connection, email: &str, page: usize) -> (Vec<i64>, bool) {
+    return pagination::post_ids(
+        dbconn,
+        "SELECT post_id FROM posts
+            WHERE status = 'published' AND publish_at <= CURRENT_TIMESTAMP
+            AND email IN (SELECT followee FROM follows WHERE follower = ?1)
+            ORDER BY publish_at DESC, post_id DESC LIMIT ?2 OFFSET ?3",
+        email,
+        page,
+    );
+}
@@ -0,0 +1,218 @@
+use json::JsonValue;
+use std::cmp::Ordering;
+
+use crate::config;
+use crate::http::cache;
+use crate::http::date;
+use crate::http::request::{find_header, find_query};
+use crate::http::static_files::{self, DirEntry};
+use crate::markdown::escape_html;
+
+// A directory with one of these files is always or never listed, whatever AUTOINDEX says
+const ENABLE_FILE: &str = ".autoindex";
+const DISABLE_FILE: &str = ".noautoindex";
+
+struct Entry {
+    name: String,
+    is_directory: bool,
+    size: u64,
+    modified: i64,
+}
+
+#[derive(PartialEq)]
+enum SortKey {
+    Name,
+    Size,
+    Modified,
+}
+
+impl SortKey {
+    fn parse(key: Option<&str>) -> SortKey {
+        return match key {
+            Some("size") => SortKey::Size,
+            Some("modified") => SortKey::Modified,
+            _ => SortKey::Name,
+        };
+    }
+
+    fn name(&self) -> &'static str {
+        return match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+        };
+    }
+}
+
+// Entries of the directory of the web root requested by the path, None for files and missing paths
+pub async fn find_directory(path: &str) -> Option<Vec<DirEntry>> {
+    return static_files::read_dir(path).await;
+}
+
+// Whether the directory has an index.html to show instead of the listing
+pub fn has_index(listing: &[DirEntry]) -> bool {
+    return listing
+        .iter()
+        .any(|entry: &DirEntry| entry.name == "index.html" && !entry.is_directory);
+}
+
+pub fn enabled(listing: &[DirEntry]) -> bool {
+    let has_file = |name: &str| -> bool {
+        return listing
+            .iter()
+            .any(|entry: &DirEntry| entry.name == name && !entry.is_directory);
+    };
+    if has_file(DISABLE_FILE) {
+        return false;
+    }
+    if has_file(ENABLE_FILE) {
+        return true;
+    }
+    return config::autoindex();
+}
+
+// Files and folders of the directory, hidden ones (starting with ".") are left out
+fn entries(listing: &[DirEntry]) -> Vec<Entry> {
+    return listing
+        .iter()
+        .filter(|entry: &&DirEntry| !entry.name.starts_with('.'))
+        .map(|entry: &DirEntry| Entry {
+            name: entry.name.clone(),
+            is_directory: entry.is_directory,
+            size: entry.size,
+            modified: cache::modified(entry.modified),
+        })
+        .collect();
+}
+
+// Folders always come first, then the entries are sorted by the key
+fn sort(entries: &mut [Entry], key: &SortKey, descending: bool) {
+    entries.sort_by(|a: &Entry, b: &Entry| {
+        let ordering: Ordering = match key {
+            SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        let ordering: Ordering = if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        return b.is_directory.cmp(&a.is_directory).then(ordering);
+    });
+}
+
+fn human_size(size: u64) -> String {
+    let units: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size: f64 = size as f64;
+    let mut unit: usize = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        return format!("{} {}", size, units[unit]);
+    }
+    return format!("{:.1} {}", size, units[unit]);
+}
+
+fn to_json(path: &str, entries: &[Entry], key: &SortKey, descending: bool) -> String {
+    let mut list: JsonValue = JsonValue::new_array();
+    for entry in entries {
+        list.push(json::object! {
+            name: entry.name.clone(),
+            "type": if entry.is_directory { "directory" } else { "file" },
+            size: entry.size,
+            modified: date::to_http(entry.modified)
+        })
+        .unwrap();
+    }
+    let listing: JsonValue = json::object! {
+        path: path,
+        sort: key.name(),
+        order: if descending { "desc" } else { "asc" },
+        entries: list
+    };
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        listing.dump()
+    );
+}
+
+fn to_html(path: &str, entries: &[Entry], key: &SortKey, descending: bool) -> String {
+    // Clicking the current sort column again reverses the order
+    let header = |column: SortKey, label: &str| -> String {
+        let order: &str = if column == *key && !descending {
+            "desc"
+        } else {
+            "asc"
+        };
+        return format!(
+            r#"<th><a href="?sort={}&amp;order={}">{}</a></th>"#,
+            column.name(),
+            order,
+            label
+        );
+    };
+
+    let mut rows: String = String::new();
+    if path != "/" {
+        rows.push_str(r#"<tr><td><a href="../">../</a></td><td></td><td></td></tr>"#);
+    }
+    for entry in entries {
+        // Folders end with a slash so the relative links of their own listing work
+        let (href, name, size): (String, String, String) = if entry.is_directory {
+            (
+                format!("{}/", urlencoding::encode(&entry.name)),
+                format!("{}/", entry.name),
+                "-".to_string(),
+            )
+        } else {
+            (
+                urlencoding::encode(&entry.name).to_string(),
+                entry.name.clone(),
+                human_size(entry.size),
+            )
+        };
+        rows.push_str(&format!(
+            r#"<tr><td><a href="{}">{}</a></td><td>{}</td><td>{}</td></tr>"#,
+            escape_html(&href),
+            escape_html(&name),
+            size,
+            date::to_http(entry.modified)
+        ));
+    }
+
+    let title: String = escape_html(&format!("Index of {}", path));
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<!doctype html><html lang=\"en\"><head><meta charset=\"UTF-8\" /><title>{title}</title></head><body><h1>{title}</h1><table><thead><tr>{}{}{}</tr></thead><tbody>{}</tbody></table></body></html>",
+        header(SortKey::Name, "Name"),
+        header(SortKey::Size, "Size"),
+        header(SortKey::Modified, "Last modified"),
+        rows,
+        title = title
+    );
+}
+
+// Listing of the directory as HTML, or as JSON with ?format=json or an Accept: application/json header.
+// ?sort=name|size|modified and ?order=asc|desc choose the order of the entries.
+pub fn render(
+    listing: &[DirEntry],
+    path: &str,
+    queries: &[(String, String)],
+    headers: &[(String, String)],
+) -> String {
+    let key: SortKey = SortKey::parse(find_query(queries, "sort"));
+    let descending: bool = find_query(queries, "order") == Some("desc");
+    let wants_json: bool = find_query(queries, "format") == Some("json")
+        || find_header(headers, "Accept")
+            .is_some_and(|accept: &str| accept.contains("application/json"));
+
+    let mut entries: Vec<Entry> = entries(listing);
+    sort(&mut entries, &key, descending);
+    if wants_json {
+        return to_json(path, &entries, &key, descending);
+    }
+    return to_html(path, &entries, &key, descending);
+}
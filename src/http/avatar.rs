@@ -0,0 +1,85 @@
+use sha256::digest;
+
+use crate::http::request::find_header;
+
+// Cells of the identicon, only the left half and the middle column are random, the rest is mirrored
+const GRID_SIZE: usize = 5;
+// The avatar of a user never changes, so it can be cached for a year
+const MAX_AGE: u64 = 365 * 24 * 60 * 60;
+
+// Bytes of the SHA-256 of the user id, the same id always gives the same avatar
+fn hash(user_id: i64) -> Vec<u8> {
+    let hex: String = digest(format!("avatar:{}", user_id));
+    return (0..hex.len())
+        .step_by(2)
+        .map(|index: usize| u8::from_str_radix(&hex[index..index + 2], 16).unwrap_or(0))
+        .collect();
+}
+
+// A symmetric 5x5 identicon with a color picked from the hash
+fn identicon(user_id: i64) -> String {
+    let hash: Vec<u8> = hash(user_id);
+    let hue: u16 = u16::from_be_bytes([hash[0], hash[1]]) % 360;
+    let saturation: u8 = 45 + hash[2] % 30;
+    let lightness: u8 = 40 + hash[3] % 20;
+
+    let mut cells: String = String::new();
+    let half: usize = GRID_SIZE.div_ceil(2);
+    for row in 0..GRID_SIZE {
+        for column in 0..half {
+            if hash[4 + row * half + column] & 1 == 0 {
+                continue;
+            }
+            cells.push_str(&format!(
+                r#"<rect x="{}" y="{}" width="1" height="1"/>"#,
+                column, row
+            ));
+            let mirrored: usize = GRID_SIZE - 1 - column;
+            if mirrored != column {
+                cells.push_str(&format!(
+                    r#"<rect x="{}" y="{}" width="1" height="1"/>"#,
+                    mirrored, row
+                ));
+            }
+        }
+    }
+
+    return format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-0.5 -0.5 {size} {size}" width="256" height="256" shape-rendering="crispEdges"><rect x="-0.5" y="-0.5" width="{size}" height="{size}" fill="hsl({hue}, 20%, 92%)"/><g fill="hsl({hue}, {saturation}%, {lightness}%)">{cells}</g></svg>"#,
+        size = GRID_SIZE + 1,
+        hue = hue,
+        saturation = saturation,
+        lightness = lightness,
+        cells = cells
+    );
+}
+
+// /avatar/<user_id>.svg, None if the file name is not a user id
+pub fn get_avatar(file: &str, headers: &[(String, String)]) -> Option<String> {
+    let user_id: i64 = file.strip_suffix(".svg")?.parse::<i64>().ok()?;
+    let svg: String = identicon(user_id);
+    let etag: String = format!("\"{}\"", digest(&svg));
+    let cache_control: String = format!("public, max-age={}, immutable", MAX_AGE);
+
+    let not_modified: bool =
+        find_header(headers, "If-None-Match").is_some_and(|if_none_match: &str| {
+            if_none_match
+                .split(',')
+                .map(|tag: &str| tag.trim().trim_start_matches("W/"))
+                .any(|tag: &str| tag == etag || tag == "*")
+        });
+    if not_modified {
+        return Some(format!(
+            "HTTP/1.1 304 NOT MODIFIED\r\nETag: {}\r\nCache-Control: {}\r\n\r\n",
+            etag, cache_control
+        ));
+    }
+
+    return Some(format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: image/svg+xml\r\nContent-Length: {}\r\nETag: {}\r\nCache-Control: {}\r\n\r\n{}",
+        svg.len(),
+        etag,
+        cache_control,
+        svg
+    ));
+}
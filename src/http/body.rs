@@ -0,0 +1,79 @@
+use std::io::SeekFrom;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+// Bytes read from the disk and written to the socket at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// A range of a file sent in a multipart/byteranges body, after its own headers
+pub struct FilePart {
+    pub head: String,
+    pub first: u64,
+    pub length: u64,
+}
+
+// What is sent after the headers of a response
+pub enum Body {
+    Bytes(Vec<u8>),
+    // Files embedded in the executable are sent without copying them
+    Static(&'static [u8]),
+    // Files are streamed in chunks so they are never fully loaded in memory,
+    // the length is the one sent in the Content-Length header
+    File(File, u64),
+    // Parts of a file with the closing boundary after the last one
+    Multipart(File, Vec<FilePart>, String),
+}
+
+impl Body {
+    pub fn empty() -> Body {
+        return Body::Bytes(Vec::new());
+    }
+
+    // Value of the Content-Length header
+    pub fn length(&self) -> u64 {
+        return match self {
+            Body::Bytes(bytes) => bytes.len() as u64,
+            Body::Static(bytes) => bytes.len() as u64,
+            Body::File(_, length) => *length,
+            Body::Multipart(_, parts, trailer) => {
+                parts
+                    .iter()
+                    .map(|part: &FilePart| part.head.len() as u64 + part.length)
+                    .sum::<u64>()
+                    + trailer.len() as u64
+            }
+        };
+    }
+
+    pub async fn write_to<W: AsyncWrite + Unpin>(self, socket: &mut W) -> std::io::Result<()> {
+        match self {
+            Body::Bytes(bytes) => {
+                if !bytes.is_empty() {
+                    socket.write_all(&bytes).await?;
+                }
+            }
+            Body::Static(bytes) => socket.write_all(bytes).await?,
+            Body::File(file, length) => {
+                let mut reader = BufReader::with_capacity(CHUNK_SIZE, file.take(length));
+                tokio::io::copy_buf(&mut reader, socket).await?;
+            }
+            Body::Multipart(mut file, parts, trailer) => {
+                for part in parts {
+                    socket.write_all(part.head.as_bytes()).await?;
+                    file.seek(SeekFrom::Start(part.first)).await?;
+                    let mut reader =
+                        BufReader::with_capacity(CHUNK_SIZE, (&mut file).take(part.length));
+                    tokio::io::copy_buf(&mut reader, socket).await?;
+                }
+                socket.write_all(trailer.as_bytes()).await?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Body {
+        return Body::Bytes(bytes);
+    }
+}
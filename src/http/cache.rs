@@ -0,0 +1,86 @@
+use sha256::digest;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config;
+use crate::http::date;
+use crate::http::request::find_header;
+
+// Seconds since the epoch of the last change of a file
+pub fn modified(modified: Option<SystemTime>) -> i64 {
+    return modified
+        .and_then(|modified: SystemTime| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_secs() as i64)
+        .unwrap_or(0);
+}
+
+// Strong ETag of a file from its modification time and size, it changes every time the file is written
+pub fn file_etag(modified: Option<SystemTime>, length: u64) -> String {
+    let nanos: u128 = modified
+        .and_then(|modified: SystemTime| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_nanos())
+        .unwrap_or(0);
+    return format!("\"{:x}-{:x}\"", nanos, length);
+}
+
+// Weak ETag of a generated body, the same content can be serialized differently
+pub fn weak_etag(body: &str) -> String {
+    return format!("W/\"{}\"", digest(body));
+}
+
+// Whether the client already has this version, If-None-Match uses the weak comparison of RFC 9110 8.8.3.2
+pub fn not_modified(headers: &[(String, String)], etag: &str, last_modified: i64) -> bool {
+    let etag: &str = etag.trim_start_matches("W/");
+    // If-None-Match takes precedence over If-Modified-Since (RFC 9110 13.2.2)
+    if let Some(if_none_match) = find_header(headers, "If-None-Match") {
+        return if_none_match
+            .split(',')
+            .map(|tag: &str| tag.trim().trim_start_matches("W/"))
+            .any(|tag: &str| tag == etag || tag == "*");
+    }
+    if let Some(if_modified_since) = find_header(headers, "If-Modified-Since") {
+        if let Some(since) = date::parse_http(if_modified_since) {
+            return last_modified <= since;
+        }
+    }
+    return false;
+}
+
+// Cache-Control of the longest configured path prefix matching the path
+pub fn policy(path: &str) -> Option<String> {
+    return config::cache_control()
+        .into_iter()
+        .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, policy)| policy);
+}
+
+// Adds a weak ETag to a successful API response, or replaces it with 304 Not Modified
+// when the client sent the same ETag. Responses depend on the logged in user, so they vary on the cookie.
+pub fn revalidate_api(response: String, path: &str, headers: &[(String, String)]) -> String {
+    if !response.starts_with("HTTP/1.1 200") {
+        return response;
+    }
+    let (head, body): (&str, &str) = match response.split_once("\r\n\r\n") {
+        Some(parts) => parts,
+        None => return response,
+    };
+
+    let etag: String = weak_etag(body);
+    let mut validators: String = format!("ETag: {}\r\nVary: Cookie\r\n", etag);
+    if let Some(policy) = policy(path) {
+        validators.push_str(&format!("Cache-Control: {}\r\n", policy));
+    }
+    // API responses have no modification date, only If-None-Match is checked
+    let if_none_match: bool = find_header(headers, "If-None-Match").is_some();
+    if if_none_match && not_modified(headers, &etag, 0) {
+        return format!("HTTP/1.1 304 NOT MODIFIED\r\n{}\r\n", validators);
+    }
+
+    let (status, fields): (&str, &str) = head.split_once("\r\n").unwrap_or((head, ""));
+    let fields: String = if fields.is_empty() {
+        String::new()
+    } else {
+        format!("{}\r\n", fields)
+    };
+    return format!("{}\r\n{}{}\r\n{}", status, fields, validators, body);
+}
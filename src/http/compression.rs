@@ -0,0 +1,207 @@
+use brotli::CompressorWriter;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use std::io::Write;
+
+use crate::http::body::Body;
+use crate::http::request::find_header;
+
+// Smaller bodies don't get any smaller once the compression headers are added
+pub const MIN_SIZE: u64 = 1024;
+// Static files are compressed in memory, larger ones are streamed as they are
+pub const MAX_SIZE: u64 = 8 * 1024 * 1024;
+// Brotli quality used on the fly, the highest ones are too slow for every request
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+
+// Types that aren't text/* but are still worth compressing
+const COMPRESSIBLE_TYPES: [&str; 8] = [
+    "application/json",
+    "application/javascript",
+    "application/xml",
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/manifest+json",
+    "application/wasm",
+    "image/svg+xml",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    Deflate,
+    Identity,
+}
+
+impl Encoding {
+    // Every encoding that can be applied on the fly, in order of preference
+    pub const DYNAMIC: [Encoding; 3] = [Encoding::Brotli, Encoding::Gzip, Encoding::Deflate];
+    // Precompressed siblings looked for next to the static files
+    pub const PRECOMPRESSED: [Encoding; 2] = [Encoding::Brotli, Encoding::Gzip];
+
+    // Value of the Content-Encoding header
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            Encoding::Identity => "identity",
+        };
+    }
+
+    // Extension of the precompressed file, e.g. js.js.br
+    pub fn extension(&self) -> &'static str {
+        return match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gz",
+            Encoding::Deflate => "zz",
+            Encoding::Identity => "",
+        };
+    }
+}
+
+fn field_name(field: &str) -> &str {
+    return field.split(':').next().unwrap_or("").trim();
+}
+
+pub fn is_compressible(content_type: &str) -> bool {
+    let mime: &str = content_type.split(';').next().unwrap_or("").trim();
+    return mime.starts_with("text/") || COMPRESSIBLE_TYPES.contains(&mime);
+}
+
+// (coding, q) entries of the Accept-Encoding header, a missing q is 1
+fn accepted(accept_encoding: &str) -> Vec<(String, f32)> {
+    return accept_encoding
+        .split(',')
+        .filter_map(|entry: &str| {
+            let mut params = entry.split(';');
+            let coding: String = params.next()?.trim().to_lowercase();
+            if coding.is_empty() {
+                return None;
+            }
+            let quality: f32 = params
+                .find_map(|param: &str| param.trim().strip_prefix("q="))
+                .and_then(|quality: &str| quality.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            return Some((coding, quality.clamp(0.0, 1.0)));
+        })
+        .collect();
+}
+
+// Picks the encoding with the highest q-value among the supported ones, ties go to the order of
+// the supported encodings. Identity is used when the client doesn't accept any of them.
+pub fn negotiate(headers: &[(String, String)], supported: &[Encoding]) -> Encoding {
+    let accepted: Vec<(String, f32)> = match find_header(headers, "Accept-Encoding") {
+        Some(accept_encoding) => accepted(accept_encoding),
+        None => return Encoding::Identity,
+    };
+    let quality = |name: &str| -> f32 {
+        let explicit = accepted.iter().find(|(coding, _)| coding == name);
+        let wildcard = accepted.iter().find(|(coding, _)| coding == "*");
+        return explicit
+            .or(wildcard)
+            .map(|(_, quality)| *quality)
+            .unwrap_or(0.0);
+    };
+
+    let mut best: (Encoding, f32) = (Encoding::Identity, 0.0);
+    for encoding in supported {
+        let quality: f32 = quality(encoding.name());
+        if quality > best.1 {
+            best = (*encoding, quality);
+        }
+    }
+    return best.0;
+}
+
+pub fn compress(body: &[u8], encoding: Encoding) -> Vec<u8> {
+    return match encoding {
+        Encoding::Brotli => {
+            let mut compressed: Vec<u8> = Vec::new();
+            {
+                let mut writer =
+                    CompressorWriter::new(&mut compressed, 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+                writer.write_all(body).unwrap();
+            }
+            compressed
+        }
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).unwrap();
+            encoder.finish().unwrap()
+        }
+        Encoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).unwrap();
+            encoder.finish().unwrap()
+        }
+        Encoding::Identity => body.to_vec(),
+    };
+}
+
+// A strong ETag must be different for every encoding of the same file
+pub fn etag(etag: &str, encoding: Encoding) -> String {
+    if encoding == Encoding::Identity {
+        return etag.to_string();
+    }
+    return format!("{}-{}\"", etag.trim_end_matches('"'), encoding.extension());
+}
+
+// Compresses a generated response when its type is compressible and it is large enough,
+// every compressible response varies on Accept-Encoding even when it is sent as it is
+pub fn compress_response(response: String, headers: &[(String, String)]) -> (String, Body) {
+    let (head, body): (&str, &str) = match response.split_once("\r\n\r\n") {
+        Some(parts) => parts,
+        None => return (response, Body::empty()),
+    };
+    let content_type: &str = head
+        .lines()
+        .find_map(|line: &str| {
+            let (key, value) = line.split_once(':')?;
+            return key
+                .eq_ignore_ascii_case("Content-Type")
+                .then_some(value.trim());
+        })
+        .unwrap_or("");
+    if !head.starts_with("HTTP/1.1 200") || !is_compressible(content_type) {
+        return (response, Body::empty());
+    }
+
+    let encoding: Encoding = if (body.len() as u64) < MIN_SIZE {
+        Encoding::Identity
+    } else {
+        negotiate(headers, &Encoding::DYNAMIC)
+    };
+    let mut fields: Vec<String> = head.split("\r\n").map(str::to_string).collect();
+    match fields
+        .iter_mut()
+        .find(|field: &&mut String| field_name(field).eq_ignore_ascii_case("Vary"))
+    {
+        Some(vary) => vary.push_str(", Accept-Encoding"),
+        None => fields.push("Vary: Accept-Encoding".to_string()),
+    }
+    if encoding == Encoding::Identity {
+        return (
+            format!("{}\r\n\r\n{}", fields.join("\r\n"), body),
+            Body::empty(),
+        );
+    }
+
+    let compressed: Vec<u8> = compress(body.as_bytes(), encoding);
+    let mut fields: Vec<String> = fields
+        .into_iter()
+        .filter(|field: &String| !field_name(field).eq_ignore_ascii_case("Content-Length"))
+        // A strong ETag would say the compressed body is the same bytes as the original one
+        .map(|field: String| match field.strip_prefix("ETag: \"") {
+            Some(etag) => format!("ETag: W/\"{}", etag),
+            None => field,
+        })
+        .collect();
+    fields.push(format!("Content-Encoding: {}", encoding.name()));
+    fields.push(format!("Content-Length: {}", compressed.len()));
+    return (
+        format!("{}\r\n\r\n", fields.join("\r\n")),
+        Body::from(compressed),
+    );
+}
@@ -0,0 +1,207 @@
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year: i64 = if month <= 2 { year - 1 } else { year };
+    let era: i64 = year.div_euclid(400);
+    let year_of_era: i64 = year - era * 400;
+    let month_index: i64 = (month + 9) % 12;
+    let day_of_year: i64 = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era: i64 = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    return era * 146097 + day_of_era - 719468;
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days: i64 = days + 719468;
+    let era: i64 = days.div_euclid(146097);
+    let day_of_era: i64 = days - era * 146097;
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: i64 = (5 * day_of_year + 2) / 153;
+    let day: i64 = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month: i64 = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year: i64 = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    return (year, month, day);
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    return match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+}
+
+// Seconds since the epoch of a date and time, None when a field is out of its range.
+// Years have 4 digits in both formats, 60 seconds is a leap second.
+fn timestamp(
+    (year, month, day): (i64, i64, i64),
+    (hours, minutes, seconds): (i64, i64, i64),
+) -> Option<i64> {
+    if !(0..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || !(0..=23).contains(&hours)
+        || !(0..=59).contains(&minutes)
+        || !(0..=60).contains(&seconds)
+    {
+        return None;
+    }
+    return days_from_civil(year, month, day)
+        .checked_mul(86400)?
+        .checked_add(hours * 3600 + minutes * 60 + seconds);
+}
+
+fn split(timestamp: i64) -> (i64, i64, i64, i64, i64, i64, usize) {
+    let days: i64 = timestamp.div_euclid(86400);
+    let seconds: i64 = timestamp.rem_euclid(86400);
+    let (year, month, day): (i64, i64, i64) = civil_from_days(days);
+    let weekday: usize = days.rem_euclid(7) as usize;
+    return (
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        weekday,
+    );
+}
+
+// Parses the "YYYY-MM-DD HH:MM:SS" (UTC) strings stored by SQLite
+pub fn parse_sql(datetime: &str) -> Option<i64> {
+    let datetime: &str = datetime.trim();
+    if datetime.len() < 19 {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| datetime.get(range)?.parse::<i64>().ok();
+
+    let year: i64 = number(0..4)?;
+    let month: i64 = number(5..7)?;
+    let day: i64 = number(8..10)?;
+    let hours: i64 = number(11..13)?;
+    let minutes: i64 = number(14..16)?;
+    let seconds: i64 = number(17..19)?;
+    return timestamp((year, month, day), (hours, minutes, seconds));
+}
+
+// Parses an IMF-fixdate ("Sun, 06 Nov 1994 08:49:37 GMT"), the only format HTTP/1.1 clients must send
+pub fn parse_http(date: &str) -> Option<i64> {
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    if parts.len() != 6 || parts[5] != "GMT" {
+        return None;
+    }
+    let day: i64 = parts[1].parse::<i64>().ok()?;
+    let month: i64 = MONTHS.iter().position(|month: &&str| *month == parts[2])? as i64 + 1;
+    let year: i64 = parts[3].parse::<i64>().ok()?;
+    let time: Vec<i64> = parts[4]
+        .split(':')
+        .map(|part: &str| part.parse::<i64>())
+        .collect::<Result<Vec<i64>, _>>()
+        .ok()?;
+    if time.len() != 3 {
+        return None;
+    }
+    return timestamp((year, month, day), (time[0], time[1], time[2]));
+}
+
+pub fn to_rfc3339(timestamp: i64) -> String {
+    let (year, month, day, hours, minutes, seconds, _) = split(timestamp);
+    return format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, hours, minutes, seconds
+    );
+}
+
+pub fn to_rfc822(timestamp: i64) -> String {
+    let (year, month, day, hours, minutes, seconds, weekday) = split(timestamp);
+    return format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} +0000",
+        DAYS[weekday],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        hours,
+        minutes,
+        seconds
+    );
+}
+
+pub fn to_http(timestamp: i64) -> String {
+    let (year, month, day, hours, minutes, seconds, weekday) = split(timestamp);
+    return format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        DAYS[weekday],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        hours,
+        minutes,
+        seconds
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_round_trip() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(days_from_civil(2000, 2, 29), 11016);
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        for days in (-800_000..800_000).step_by(97) {
+            let (year, month, day): (i64, i64, i64) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(to_http(784111777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(to_rfc822(784111777), "Sun, 06 Nov 1994 08:49:37 +0000");
+        assert_eq!(to_rfc3339(784111777), "1994-11-06T08:49:37Z");
+        assert_eq!(to_http(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(to_rfc3339(951782400), "2000-02-29T00:00:00Z");
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        for timestamp in [0, 784111777, 951782400, 1_700_000_000, 253402300799] {
+            assert_eq!(parse_http(&to_http(timestamp)), Some(timestamp));
+        }
+        assert_eq!(parse_sql("1994-11-06 08:49:37"), Some(784111777));
+        assert_eq!(parse_sql("2000-02-29 00:00:00.123"), Some(951782400));
+    }
+
+    #[test]
+    fn invalid_dates() {
+        assert_eq!(parse_http("Sun, 06 Nov 99999999999999 08:49:37 GMT"), None);
+        assert_eq!(parse_http("Sun, 06 Nov -1 08:49:37 GMT"), None);
+        assert_eq!(
+            parse_http("Sun, 99999999999999 Nov 1994 08:49:37 GMT"),
+            None
+        );
+        assert_eq!(parse_http("Sun, 31 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http("Sun, 29 Feb 1900 08:49:37 GMT"), None);
+        assert_eq!(parse_http("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+        assert_eq!(
+            parse_http("Sun, 06 Nov 1994 08:99999999999999:37 GMT"),
+            None
+        );
+        assert_eq!(parse_http("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(parse_http("Sun, 06 Nov 1994 08:49 GMT"), None);
+        assert_eq!(parse_sql("1994-13-06 08:49:37"), None);
+        assert_eq!(parse_sql("1994-11-06 08:61:37"), None);
+        assert_eq!(parse_sql("1994-11-06"), None);
+    }
+}
@@ -0,0 +1,270 @@
+use rusqlite::Connection;
+use sha256::digest;
+
+use crate::config;
+use crate::db::dbconn;
+use crate::http::cache;
+use crate::http::date;
+use crate::http::request::find_query;
+use crate::http::static_files::{self, StaticFile};
+use crate::markdown::escape_html;
+use crate::profiles::{self, Profile};
+use crate::tags;
+
+const FEED_TITLE: &str = "Rust Blog";
+const FEED_DESCRIPTION: &str = "Posts from the Rust Blog";
+const FEED_LENGTH: usize = 50;
+
+struct FeedPost {
+    post_id: i64,
+    title: String,
+    content_html: String,
+    author: String,
+    published: i64,
+    image: String,
+    tags: Vec<String>,
+}
+
+struct Enclosure {
+    url: String,
+    length: u64,
+    mime: String,
+}
+
+async fn enclosure(base_url: &str, image: &str) -> Option<Enclosure> {
+    if image.is_empty() {
+        return None;
+    }
+    let file: StaticFile = static_files::open(image).await.ok()?;
+    return Some(Enclosure {
+        url: format!("{}{}", base_url, image),
+        length: file.length,
+        mime: static_files::mime_type(image).to_string(),
+    });
+}
+
+fn query_posts(author: &Option<String>, tag: &Option<String>) -> Vec<FeedPost> {
+    let dbconn: Connection = dbconn();
+    // Authors are looked up by handle, an unknown handle matches no post
+    let author: Option<String> = author.as_ref().map(|handle: &String| {
+        profiles::by_handle(&dbconn, handle)
+            .map(|profile: Profile| profile.email)
+            .unwrap_or_default()
+    });
+    let mut stmt = dbconn
+        .prepare(
+            "SELECT post_id, title, content_html, email, publish_at, image FROM posts
+                WHERE status = 'published' AND publish_at <= CURRENT_TIMESTAMP
+                AND (?1 IS NULL OR email = ?1)
+                AND (?2 IS NULL OR post_id IN (
+                    SELECT post_tags.post_id FROM post_tags JOIN tags ON tags.tag_id = post_tags.tag_id WHERE tags.name = ?2
+                ))
+                ORDER BY publish_at DESC LIMIT ?3",
+        )
+        .unwrap();
+    let posts_iter = stmt.query_map(
+        [
+            &author as &dyn rusqlite::ToSql,
+            tag as &dyn rusqlite::ToSql,
+            &FEED_LENGTH as &dyn rusqlite::ToSql,
+        ],
+        |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        },
+    );
+
+    let mut posts: Vec<FeedPost> = Vec::new();
+    for post in posts_iter.unwrap() {
+        let post = post.unwrap();
+        posts.push(FeedPost {
+            post_id: post.0,
+            title: post.1,
+            content_html: post.2,
+            author: profiles::ensure(&dbconn, &post.3).name(),
+            published: date::parse_sql(&post.4).unwrap_or(0),
+            image: post.5,
+            tags: tags::of_post(&dbconn, post.0)
+                .members()
+                .map(|tag| tag.to_string())
+                .collect(),
+        });
+    }
+    return posts;
+}
+
+fn feed_title(author: &Option<String>, tag: &Option<String>) -> String {
+    let mut title: String = FEED_TITLE.to_string();
+    if let Some(author) = author {
+        title.push_str(&format!(" - @{}", author));
+    }
+    if let Some(tag) = tag {
+        title.push_str(&format!(" - #{}", tag));
+    }
+    return title;
+}
+
+fn feed_query(author: &Option<String>, tag: &Option<String>) -> String {
+    let mut query: Vec<String> = Vec::new();
+    if let Some(author) = author {
+        query.push(format!("author={}", urlencoding::encode(author)));
+    }
+    if let Some(tag) = tag {
+        query.push(format!("tag={}", urlencoding::encode(tag)));
+    }
+    if query.is_empty() {
+        return String::new();
+    }
+    return format!("?{}", query.join("&"));
+}
+
+async fn rss(
+    posts: &[FeedPost],
+    base_url: &str,
+    author: &Option<String>,
+    tag: &Option<String>,
+    updated: i64,
+) -> String {
+    let mut items: String = String::new();
+    for post in posts {
+        let link: String = format!("{}/post/{}", base_url, post.post_id);
+        items.push_str(&format!(
+            "<item><title>{}</title><link>{}</link><guid isPermaLink=\"true\">{}</guid><dc:creator>{}</dc:creator><pubDate>{}</pubDate>",
+            escape_html(&post.title),
+            escape_html(&link),
+            escape_html(&link),
+            escape_html(&post.author),
+            date::to_rfc822(post.published)
+        ));
+        for tag in &post.tags {
+            items.push_str(&format!("<category>{}</category>", escape_html(tag)));
+        }
+        if let Some(enclosure) = enclosure(base_url, &post.image).await {
+            items.push_str(&format!(
+                "<enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>",
+                escape_html(&enclosure.url),
+                enclosure.length,
+                escape_html(&enclosure.mime)
+            ));
+        }
+        items.push_str(&format!(
+            "<description>{}</description></item>",
+            escape_html(&post.content_html)
+        ));
+    }
+
+    return format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\"><channel><title>{}</title><link>{}/</link><description>{}</description><atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/><lastBuildDate>{}</lastBuildDate>{}</channel></rss>\n",
+        escape_html(&feed_title(author, tag)),
+        escape_html(base_url),
+        escape_html(FEED_DESCRIPTION),
+        escape_html(&format!("{}/feed.rss{}", base_url, feed_query(author, tag))),
+        date::to_rfc822(updated),
+        items
+    );
+}
+
+async fn atom(
+    posts: &[FeedPost],
+    base_url: &str,
+    author: &Option<String>,
+    tag: &Option<String>,
+    updated: i64,
+) -> String {
+    let mut entries: String = String::new();
+    for post in posts {
+        let link: String = format!("{}/post/{}", base_url, post.post_id);
+        entries.push_str(&format!(
+            "<entry><title>{}</title><link rel=\"alternate\" href=\"{}\"/><id>{}</id><published>{}</published><updated>{}</updated><author><name>{}</name></author>",
+            escape_html(&post.title),
+            escape_html(&link),
+            escape_html(&link),
+            date::to_rfc3339(post.published),
+            date::to_rfc3339(post.published),
+            escape_html(&post.author)
+        ));
+        for tag in &post.tags {
+            entries.push_str(&format!("<category term=\"{}\"/>", escape_html(tag)));
+        }
+        if let Some(enclosure) = enclosure(base_url, &post.image).await {
+            entries.push_str(&format!(
+                "<link rel=\"enclosure\" href=\"{}\" length=\"{}\" type=\"{}\"/>",
+                escape_html(&enclosure.url),
+                enclosure.length,
+                escape_html(&enclosure.mime)
+            ));
+        }
+        entries.push_str(&format!(
+            "<content type=\"html\">{}</content></entry>",
+            escape_html(&post.content_html)
+        ));
+    }
+
+    let self_url: String = format!("{}/feed.atom{}", base_url, feed_query(author, tag));
+    return format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\"><title>{}</title><subtitle>{}</subtitle><id>{}</id><link rel=\"self\" href=\"{}\"/><link rel=\"alternate\" href=\"{}/\"/><updated>{}</updated>{}</feed>\n",
+        escape_html(&feed_title(author, tag)),
+        escape_html(FEED_DESCRIPTION),
+        escape_html(&self_url),
+        escape_html(&self_url),
+        escape_html(base_url),
+        date::to_rfc3339(updated),
+        entries
+    );
+}
+
+pub async fn get_feed(
+    format: &str,
+    queries: &[(String, String)],
+    headers: &[(String, String)],
+) -> String {
+    let author: Option<String> = find_query(queries, "author")
+        .filter(|author: &&str| !author.is_empty())
+        .map(|author: &str| author.trim_start_matches('@').to_lowercase());
+    let tag: Option<String> = find_query(queries, "tag")
+        .filter(|tag: &&str| !tag.is_empty())
+        .map(tags::normalize);
+
+    let base_url: String = config::public_url(headers);
+    let posts: Vec<FeedPost> = query_posts(&author, &tag);
+    let updated: i64 = posts
+        .iter()
+        .map(|post: &FeedPost| post.published)
+        .max()
+        .unwrap_or(0);
+
+    let (body, content_type): (String, &str) = match format {
+        "rss" => (
+            rss(&posts, &base_url, &author, &tag, updated).await,
+            "application/rss+xml",
+        ),
+        _ => (
+            atom(&posts, &base_url, &author, &tag, updated).await,
+            "application/atom+xml",
+        ),
+    };
+
+    let etag: String = format!("\"{}\"", digest(&body));
+    let last_modified: String = date::to_http(updated);
+    if cache::not_modified(headers, &etag, updated) {
+        return format!(
+            "HTTP/1.1 304 NOT MODIFIED\r\nETag: {}\r\nLast-Modified: {}\r\n\r\n",
+            etag, last_modified
+        );
+    }
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nETag: {}\r\nLast-Modified: {}\r\n\r\n{}",
+        content_type,
+        body.len(),
+        etag,
+        last_modified,
+        body
+    );
+}
@@ -0,0 +1,84 @@
+use json::JsonValue;
+use rusqlite::Connection;
+
+use crate::bookmarks;
+use crate::db::dbconn;
+use crate::follows;
+use crate::http::token;
+use crate::profiles::{self, Profile};
+
+async fn delete_follow(handle: &str, sha256_token: &str) -> String {
+    let decoded: JsonValue = token::get_userdata(sha256_token).await;
+    if decoded["email"].is_null() {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
+    let email: &str = decoded["email"].as_str().unwrap();
+
+    let dbconn: Connection = dbconn();
+    let author: Profile = match profiles::by_handle(&dbconn, handle) {
+        Some(author) => author,
+        None => return "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found",
+    };
+    follows::unfollow(&dbconn, email, &author.email);
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        json::object! {
+            status: "ok",
+            following: false,
+            followers: follows::counts(&dbconn, &author.email)["followers"].clone()
+        }
+        .dump()
+    );
+}
+
+async fn delete_bookmark(post_id: &str, sha256_token: &str) -> String {
+    let decoded: JsonValue = token::get_userdata(sha256_token).await;
+    if decoded["email"].is_null() {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
+    let email: &str = decoded["email"].as_str().unwrap();
+
+    let post_id: i64 = match post_id.parse::<i64>() {
+        Ok(post_id) => post_id,
+        Err(_) => return "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found",
+    };
+    bookmarks::remove(&dbconn(), email, post_id);
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        json::object! { status: "ok", bookmarked: false }.dump()
+    );
+}
+
+pub async fn delete(path: String, headers: Vec<(String, String)>) -> (String, Vec<u8>) {
+    let sha256_token: &str = match headers
+        .iter()
+        .find(|header: &&(String, String)| header.0 == "Cookie")
+    {
+        Some(header) => header
+            .1
+            .split("token=")
+            .collect::<Vec<&str>>()
+            .last()
+            .unwrap(),
+        None => "",
+    };
+
+    let content: String = match path.as_str() {
+        _ if path.starts_with("/api/users/") && path.ends_with("/follow") => {
+            let handle: &str = path
+                .trim_start_matches("/api/users/")
+                .trim_end_matches("/follow");
+            delete_follow(handle, sha256_token).await
+        }
+        _ if path.starts_with("/api/posts/") && path.ends_with("/bookmark") => {
+            let post_id: &str = path
+                .trim_start_matches("/api/posts/")
+                .trim_end_matches("/bookmark");
+            delete_bookmark(post_id, sha256_token).await
+        }
+        _ => "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found",
+    };
+    return (content, Vec::new());
+}
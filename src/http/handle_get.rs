@@ -1,13 +1,32 @@
 use colored::Colorize;
 use json::JsonValue;
 use rusqlite::Connection;
-use tokio::fs;
 
+use crate::bookmarks;
+use crate::comments;
 use crate::db::dbconn;
+use crate::follows;
+use crate::http::autoindex;
+use crate::http::avatar;
+use crate::http::body::Body;
+use crate::http::cache;
+use crate::http::compression::{self, Encoding};
+use crate::http::date;
+use crate::http::feed;
+use crate::http::permalink;
+use crate::http::range::{self, Ranges};
+use crate::http::request::{find_header, find_query};
+use crate::http::static_files::{self, DirEntry, ResolveError, StaticFile};
 use crate::http::token::auth_token;
 use crate::http::token::get_userdata;
-
-async fn check_template(contents: &mut String, userdata: JsonValue) -> String {
+use crate::http::user_page;
+use crate::markdown::escape_html;
+use crate::notifications;
+use crate::profiles;
+use crate::reactions::{self, Target};
+use crate::tags;
+
+pub async fn check_template(contents: &mut String, userdata: JsonValue) -> String {
     for (key, value) in userdata.entries() {
         let key: String = key.to_string();
         let value: String = value.to_string();
@@ -18,168 +37,491 @@ async fn check_template(contents: &mut String, userdata: JsonValue) -> String {
     return contents.to_string();
 }
 
-async fn api_posts() -> String {
-    let mut posts: String = "[".to_string();
+// A published post as returned by /api/posts, None if it is not visible
+pub fn post_json(dbconn: &Connection, post_id: i64, show_emails: bool) -> Option<JsonValue> {
+    let post: (String, String, String, String, String, String) = dbconn
+        .query_row(
+            "SELECT title, content, content_html, email, publish_at, image FROM posts
+                WHERE post_id = ?1 AND status = 'published' AND publish_at <= CURRENT_TIMESTAMP",
+            [post_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                    row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            },
+        )
+        .ok()?;
+
+    return Some(json::object! {
+        post_id: post_id,
+        title: post.0,
+        content: post.1,
+        content_html: post.2,
+        author: profiles::author_json(dbconn, &post.3, show_emails),
+        datetime: post.4 + " UTC",
+        image: post.5,
+        tags: tags::of_post(dbconn, post_id),
+        comments: comments::tree(dbconn, post_id, show_emails),
+        reactions: reactions::counts(dbconn, &Target::Post(post_id))
+    });
+}
+
+// post_json with whether the current user bookmarked the post
+fn viewer_post_json(
+    dbconn: &Connection,
+    post_id: i64,
+    email: &str,
+    show_emails: bool,
+) -> Option<JsonValue> {
+    let mut post: JsonValue = post_json(dbconn, post_id, show_emails)?;
+    post["bookmarked"] = bookmarks::is_bookmarked(dbconn, email, post_id).into();
+    return Some(post);
+}
+
+async fn api_posts(tag: Option<String>, email: String, show_emails: bool) -> String {
+    let mut posts: JsonValue = JsonValue::new_array();
+    let dbconn: Connection = dbconn();
+
+    let mut stmt = dbconn
+        .prepare(
+            "SELECT post_id FROM posts
+                WHERE status = 'published' AND publish_at <= CURRENT_TIMESTAMP AND (?1 IS NULL OR post_id IN (
+                    SELECT post_tags.post_id FROM post_tags JOIN tags ON tags.tag_id = post_tags.tag_id WHERE tags.name = ?1
+                ))
+                ORDER BY publish_at DESC",
+        )
+        .unwrap();
+    let posts_iter = stmt.query_map([tag.map(|tag: String| tags::normalize(&tag))], |row| {
+        row.get::<_, i64>(0)
+    });
+
+    for post_id in posts_iter.unwrap() {
+        if let Some(post) = viewer_post_json(&dbconn, post_id.unwrap(), &email, show_emails) {
+            posts.push(post).unwrap();
+        }
+    }
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        posts.dump()
+    );
+}
+
+// A page of /api/feed or /api/bookmarks
+fn posts_page(
+    dbconn: &Connection,
+    email: &str,
+    page: usize,
+    (post_ids, has_more): (Vec<i64>, bool),
+    show_emails: bool,
+) -> String {
+    let mut posts: JsonValue = JsonValue::new_array();
+    for post_id in post_ids {
+        if let Some(post) = viewer_post_json(dbconn, post_id, email, show_emails) {
+            posts.push(post).unwrap();
+        }
+    }
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        json::object! { page: page, has_more: has_more, posts: posts }.dump()
+    );
+}
+
+async fn api_feed(email: String, page: usize, show_emails: bool) -> String {
+    if email == "null" {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
+
+    let dbconn: Connection = dbconn();
+    let posts: (Vec<i64>, bool) = follows::feed(&dbconn, &email, page);
+    return posts_page(&dbconn, &email, page, posts, show_emails);
+}
+
+async fn api_bookmarks(email: String, page: usize, show_emails: bool) -> String {
+    if email == "null" {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
+
     let dbconn: Connection = dbconn();
+    let posts: (Vec<i64>, bool) = bookmarks::list(&dbconn, &email, page);
+    return posts_page(&dbconn, &email, page, posts, show_emails);
+}
+
+async fn api_drafts(email: String) -> String {
+    if email == "null" {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
 
+    let mut drafts: JsonValue = JsonValue::new_array();
+    let dbconn: Connection = dbconn();
     let mut stmt = dbconn
         .prepare(
-            "SELECT post_id, title, content, email, datetime, image FROM posts ORDER BY datetime DESC",
+            "SELECT post_id, title, content, content_html, status, publish_at, datetime, image FROM posts
+                WHERE email = ?1 AND status != 'published'
+                ORDER BY COALESCE(publish_at, datetime) DESC",
         )
         .unwrap();
-    let posts_iter = stmt.query_map([], |row| {
+    let drafts_iter = stmt.query_map([&email], |row| {
         Ok((
             row.get::<_, i64>(0)?,
             row.get::<_, String>(1)?,
-            row.get::<_, String>(2)?,
-            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            row.get::<_, Option<String>>(3)?.unwrap_or_default(),
             row.get::<_, String>(4)?,
-            row.get::<_, String>(5)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, String>(6)?,
+            row.get::<_, String>(7)?,
         ))
     });
 
-    for post in posts_iter.unwrap() {
-        let post = post.unwrap();
-        let post_id: i64 = post.0;
-        let title: String = post.1;
-        let content: String = post.2;
-        let email: String = post.3;
-        let datetime: String = post.4 + " UTC";
-        let image: String = post.5;
-
-        let mut stmt = dbconn
-            .prepare("SELECT email, content, datetime FROM comments WHERE post_id = ? ORDER BY datetime DESC")
+    for draft in drafts_iter.unwrap() {
+        let draft = draft.unwrap();
+        drafts
+            .push(json::object! {
+                post_id: draft.0,
+                title: draft.1,
+                content: draft.2,
+                content_html: draft.3,
+                status: draft.4,
+                publish_at: draft.5.map(|publish_at: String| publish_at + " UTC"),
+                datetime: draft.6 + " UTC",
+                image: draft.7,
+                tags: tags::of_post(&dbconn, draft.0)
+            })
             .unwrap();
-        let comments_iter = stmt.query_map([post_id], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, String>(2)?,
-            ))
-        });
-
-        let mut comments: String = "[".to_string();
-        for comment in comments_iter.unwrap() {
-            let comment = comment.unwrap();
-            let email: String = comment.0;
-            let content: String = comment.1;
-            let datetime: String = comment.2 + " UTC";
-            comments.push_str(&format!(
-                r#"{{"email":"{}","content":"{}","datetime":"{}"}},"#,
-                email, content, datetime
-            ));
-        }
-        if comments.chars().last().unwrap() == ',' {
-            comments.pop();
-        }
-        comments.push_str("]");
+    }
 
-        let mut stmt = dbconn
-            .prepare(
-                "SELECT type, COUNT(type) AS count FROM reactions WHERE post_id = ? GROUP BY type",
-            )
-            .unwrap();
-        let reactions_iter = stmt.query_map([post_id], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
-        });
-
-        let mut reactions: String = "{".to_string();
-        for reaction in reactions_iter.unwrap() {
-            let reaction = reaction.unwrap();
-            let reaction_type: String = reaction.0;
-            let reaction_count: i64 = reaction.1;
-            reactions.push_str(&format!(r#""{}":{},"#, reaction_type, reaction_count));
-        }
-        if reactions.chars().last().unwrap() == ',' {
-            reactions.pop();
-        }
-        reactions.push_str("}");
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        drafts.dump()
+    );
+}
 
-        posts.push_str(&format!(
-            r#"{{"post_id":{},"title":"{}","content":"{}","email":"{}","datetime":"{}","image":"{}","comments":{},"reactions":{}}},"#,
-            post_id, title, content, email, datetime, image, comments, reactions
-        ));
-    }
+async fn api_tags() -> String {
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        tags::with_counts(&dbconn()).dump()
+    );
+}
 
-    if posts.chars().last().unwrap() == ',' {
-        posts.pop();
-    }
-    posts.push_str("]");
+async fn api_reactions() -> String {
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        reactions::kinds_json().dump()
+    );
+}
+
+async fn api_reactors(target: Option<Target>, show_emails: bool) -> String {
+    let target: Target = match target {
+        Some(target) => target,
+        None => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request",
+    };
 
     return format!(
         "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
-        posts
+        reactions::reactors(&dbconn(), &target, show_emails).dump()
     );
 }
 
 async fn api_userreaction(post_id: i64, email: String) -> String {
     let dbconn: Connection = dbconn();
+    let reaction: Option<String> =
+        reactions::user_reaction(&dbconn, &Target::Post(post_id), &email);
+
     let mut stmt = dbconn
-        .prepare("SELECT type FROM reactions WHERE post_id = ? AND email = ?")
+        .prepare(
+            "SELECT comment_reactions.comment_id, comment_reactions.type FROM comment_reactions
+                JOIN comments ON comments.comment_id = comment_reactions.comment_id
+                WHERE comments.post_id = ?1 AND comment_reactions.email = ?2",
+        )
         .unwrap();
-    let reaction_iter = stmt.query_map(
+    let comment_reactions_iter = stmt.query_map(
         [
             &post_id as &dyn rusqlite::types::ToSql,
             &email as &dyn rusqlite::types::ToSql,
         ],
-        |row| Ok(row.get::<_, String>(0)?),
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
     );
 
-    let mut reaction: String = "null".to_string();
-    for reaction_type in reaction_iter.unwrap() {
-        reaction = reaction_type.unwrap();
+    let mut comments: JsonValue = JsonValue::new_object();
+    for comment_reaction in comment_reactions_iter.unwrap() {
+        let comment_reaction = comment_reaction.unwrap();
+        comments[comment_reaction.0.to_string()] = comment_reaction.1.into();
     }
 
     return format!(
-        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{{\"type\":\"{}\"}}",
-        reaction
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        json::object! { type: reaction, comments: comments }.dump()
     );
 }
 
-async fn api_comments(post_id: i64) -> String {
-    let dbconn: Connection = dbconn();
-    let mut stmt = dbconn
-        .prepare("SELECT email, content, datetime FROM comments WHERE post_id = ? ORDER BY datetime DESC")
-        .unwrap();
-    let comments_iter = stmt.query_map([post_id], |row| {
-        Ok((
-            row.get::<_, String>(0)?,
-            row.get::<_, String>(1)?,
-            row.get::<_, String>(2)?,
-        ))
-    });
+async fn api_notifications(email: String, unread_only: bool) -> String {
+    if email == "null" {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
 
-    let mut comments: String = "[".to_string();
-    for comment in comments_iter.unwrap() {
-        let comment = comment.unwrap();
-        let email: String = comment.0;
-        let content: String = comment.1;
-        let datetime: String = comment.2 + " UTC";
-        comments.push_str(&format!(
-            r#"{{"email":"{}","content":"{}","datetime":"{}"}}"#,
-            email, content, datetime
-        ));
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        notifications::list(&dbconn(), &email, unread_only).dump()
+    );
+}
+
+async fn api_me(email: String) -> String {
+    if email == "null" {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
     }
-    comments.push_str("]");
 
+    let dbconn: Connection = dbconn();
+    let mut profile: JsonValue = profiles::ensure(&dbconn, &email).to_json();
+    for (key, count) in follows::counts(&dbconn, &email).entries() {
+        profile[key] = count.clone();
+    }
     return format!(
         "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
-        comments
+        profile.dump()
     );
 }
 
-async fn get_ascii_content(directory: &str, file: &str) -> String {
-    let contents: String = fs::read_to_string(format!("public/{}/{}", directory, file))
-        .await
-        .unwrap();
-    return format!("HTTP/1.1 200 OK\r\n\r\n{}", contents);
+async fn api_comments(post_id: i64, show_emails: bool) -> String {
+    let comments: JsonValue = comments::tree(&dbconn(), post_id, show_emails);
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        comments.dump()
+    );
 }
 
-async fn get_utf8_content(directory: &str, file: &str) -> Vec<u8> {
-    let contents: Vec<u8> = fs::read(format!("public/{}/{}", directory, file))
+async fn not_found() -> String {
+    let contents: String = static_files::read_to_string("pages/404.html")
         .await
-        .unwrap();
-    return contents;
+        .unwrap_or_default();
+    if contents.is_empty() {
+        return "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found";
+    }
+    return format!(
+        "HTTP/1.1 404 NOT FOUND\r\nContent-Type: text/html; charset=utf-8\r\n\r\n{}",
+        contents
+    );
+}
+
+async fn resolve_error(error: ResolveError) -> String {
+    return match error {
+        ResolveError::BadRequest => {
+            "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request"
+        }
+        ResolveError::NotFound => not_found().await,
+    };
+}
+
+async fn get_ascii_content(directory: &str, file: &str) -> String {
+    return match static_files::read_to_string(&format!("{}/{}", directory, file)).await {
+        Ok(contents) => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nX-Content-Type-Options: nosniff\r\n\r\n{}",
+            static_files::content_type(file),
+            contents
+        ),
+        Err(error) => resolve_error(error).await,
+    };
+}
+
+async fn open_file(directory: &str, file: &str) -> Result<StaticFile, ResolveError> {
+    return static_files::open(&format!("{}/{}", directory, file)).await;
+}
+
+// The .br or .gz sibling of a file with the best encoding accepted by the client
+async fn get_precompressed(
+    directory: &str,
+    file: &str,
+    headers: &[(String, String)],
+) -> Option<(StaticFile, Encoding)> {
+    let mut available: Vec<Encoding> = Vec::new();
+    for encoding in Encoding::PRECOMPRESSED {
+        let sibling: String = format!("{}.{}", file, encoding.extension());
+        if open_file(directory, &sibling).await.is_ok() {
+            available.push(encoding);
+        }
+    }
+
+    let encoding: Encoding = compression::negotiate(headers, &available);
+    if encoding == Encoding::Identity {
+        return None;
+    }
+    let sibling: String = format!("{}.{}", file, encoding.extension());
+    let contents: StaticFile = open_file(directory, &sibling).await.ok()?;
+    return Some((contents, encoding));
+}
+
+// Any file that isn't a page, streamed as it is without going through the templates.
+// A Range header gets only the requested parts of the file, compressed files are only sent without it.
+async fn get_static_file(
+    requested_endpoint: &(String, String),
+    path: &str,
+    headers: &[(String, String)],
+) -> (String, Body) {
+    let (directory, file): (&str, &str) = (&requested_endpoint.0, &requested_endpoint.1);
+    let mut contents: StaticFile = match open_file(directory, file).await {
+        Ok(opened) => opened,
+        Err(error) => return (resolve_error(error).await, Body::empty()),
+    };
+    let content_type: String = static_files::content_type(file);
+    let compressible: bool = compression::is_compressible(&content_type);
+
+    let ranged: bool = find_header(headers, "Range").is_some();
+    let mut encoding: Encoding = Encoding::Identity;
+    let mut precompressed: bool = false;
+    if !ranged {
+        if let Some(sibling) = get_precompressed(directory, file, headers).await {
+            (contents, encoding) = sibling;
+            precompressed = true;
+        } else if compressible
+            && (compression::MIN_SIZE..=compression::MAX_SIZE).contains(&contents.length)
+        {
+            encoding = compression::negotiate(headers, &Encoding::DYNAMIC);
+        }
+    }
+
+    let length: u64 = contents.length;
+    let modified: i64 = cache::modified(contents.modified);
+    let etag: String = compression::etag(&cache::file_etag(contents.modified, length), encoding);
+    let mut validators: String = format!(
+        "ETag: {}\r\nLast-Modified: {}\r\n",
+        etag,
+        date::to_http(modified)
+    );
+    if let Some(policy) = cache::policy(path) {
+        validators.push_str(&format!("Cache-Control: {}\r\n", policy));
+    }
+    if compressible || precompressed {
+        validators.push_str("Vary: Accept-Encoding\r\n");
+    }
+    // Uploaded files are never run as a document, even when opened directly
+    if contents.uploaded {
+        validators.push_str("Content-Security-Policy: default-src 'none'; sandbox\r\n");
+    }
+    if cache::not_modified(headers, &etag, modified) {
+        return (
+            format!("HTTP/1.1 304 NOT MODIFIED\r\n{}\r\n", validators),
+            Body::empty(),
+        );
+    }
+
+    if encoding != Encoding::Identity {
+        let body: Body = if precompressed {
+            match contents.body(0, length).await {
+                Ok(body) => body,
+                Err(_) => return (not_found().await, Body::empty()),
+            }
+        } else {
+            match contents.read().await {
+                Ok(original) => Body::from(compression::compress(&original, encoding)),
+                Err(_) => return (not_found().await, Body::empty()),
+            }
+        };
+        return (
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Encoding: {}\r\nContent-Length: {}\r\n{}Accept-Ranges: bytes\r\nX-Content-Type-Options: nosniff\r\n\r\n",
+                content_type,
+                encoding.name(),
+                body.length(),
+                validators
+            ),
+            body,
+        );
+    }
+
+    match range::parse(headers, length, &etag, modified) {
+        Ranges::Full => {
+            let body: Body = match contents.body(0, length).await {
+                Ok(body) => body,
+                Err(_) => return (not_found().await, Body::empty()),
+            };
+            return (
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Accept-Ranges: bytes\r\nX-Content-Type-Options: nosniff\r\n\r\n",
+                    content_type, length, validators
+                ),
+                body,
+            );
+        }
+        Ranges::Unsatisfiable => {
+            return (
+                format!(
+                    "HTTP/1.1 416 RANGE NOT SATISFIABLE\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\n\r\n",
+                    length
+                ),
+                Body::empty(),
+            );
+        }
+        Ranges::Partial(ranges) if ranges.len() == 1 => {
+            let (first, last): (u64, u64) = ranges[0];
+            let body: Body = match contents.body(first, last - first + 1).await {
+                Ok(body) => body,
+                Err(_) => return (not_found().await, Body::empty()),
+            };
+            return (
+                format!(
+                    "HTTP/1.1 206 PARTIAL CONTENT\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\n{}Accept-Ranges: bytes\r\nX-Content-Type-Options: nosniff\r\n\r\n",
+                    content_type,
+                    first,
+                    last,
+                    length,
+                    last - first + 1,
+                    validators
+                ),
+                body,
+            );
+        }
+        Ranges::Partial(ranges) => {
+            let (boundary, body): (String, Body) =
+                range::byteranges(contents.source, &ranges, length, &content_type);
+            return (
+                format!(
+                    "HTTP/1.1 206 PARTIAL CONTENT\r\nContent-Type: multipart/byteranges; boundary={}\r\nContent-Length: {}\r\n{}Accept-Ranges: bytes\r\nX-Content-Type-Options: nosniff\r\n\r\n",
+                    boundary,
+                    body.length(),
+                    validators
+                ),
+                body,
+            );
+        }
+    }
+}
+
+// Pages are rendered with the templates, the other files in the web root are sent as they are
+fn is_static_file(requested_endpoint: &(String, String)) -> bool {
+    if ["api", "feed", "post", "tag", "avatar", "user"].contains(&requested_endpoint.0.as_str()) {
+        return false;
+    }
+    return match requested_endpoint.1.rsplit_once('.') {
+        Some((_, extension)) => !extension.eq_ignore_ascii_case("html"),
+        None => false,
+    };
+}
+
+async fn get_tag_page(tag: &str) -> String {
+    let tag: String = match urlencoding::decode(tag) {
+        Ok(tag) => tags::normalize(&tag),
+        Err(_) => String::new(),
+    };
+    if tag.is_empty() {
+        return not_found().await;
+    }
+
+    let contents: String = get_ascii_content("pages", "tag.html").await;
+    return contents.replace("&{tag}", &tag);
+}
+
+fn redirect(location: &str) -> String {
+    return format!(
+        "HTTP/1.1 301 MOVED PERMANENTLY\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+        location
+    ) + "301 Moved Permanently";
 }
 
 fn match_type(path: &str) -> (String, String) {
@@ -187,6 +529,27 @@ fn match_type(path: &str) -> (String, String) {
         return ("pages".to_string(), "index.html".to_string());
     }
 
+    if path == "/feed.rss" || path == "/feed.atom" {
+        let format: &str = path.trim_start_matches("/feed.");
+        return ("feed".to_string(), format.to_string());
+    }
+
+    if let Some(post_id) = path.strip_prefix("/post/") {
+        return ("post".to_string(), post_id.to_string());
+    }
+
+    if let Some(tag) = path.strip_prefix("/tag/") {
+        return ("tag".to_string(), tag.to_string());
+    }
+
+    if let Some(avatar) = path.strip_prefix("/avatar/") {
+        return ("avatar".to_string(), avatar.to_string());
+    }
+
+    if let Some(handle) = path.strip_prefix("/u/") {
+        return ("user".to_string(), handle.to_string());
+    }
+
     if !path.contains(".") {
         if path.contains("api") {
             let file: String = path
@@ -224,6 +587,7 @@ async fn match_plain_content(
     let auth: bool = auth_token(&sha256_token).await;
     let decoded: JsonValue = get_userdata(&sha256_token).await;
     let email: String = decoded["email"].to_string();
+    let show_emails: bool = auth && profiles::is_admin(&email);
 
     if requested_endpoint.0 == "api" {
         let post_id = queries
@@ -233,40 +597,64 @@ async fn match_plain_content(
             .1
             .parse::<i64>()
             .unwrap();
+        let page: usize = find_query(&queries, "page")
+            .and_then(|page: &str| page.parse::<usize>().ok())
+            .filter(|page: &usize| *page > 0)
+            .unwrap_or(1);
         match requested_endpoint.1.as_str() {
-            "posts" => return api_posts().await,
-            "comments" => return api_comments(post_id).await,
+            "posts" => {
+                let tag: Option<String> = find_query(&queries, "tag").map(String::from);
+                return api_posts(tag, email, show_emails).await;
+            }
+            "tags" => return api_tags().await,
+            "feed" => return api_feed(email, page, show_emails).await,
+            "bookmarks" => return api_bookmarks(email, page, show_emails).await,
+            "drafts" => return api_drafts(email).await,
+            "comments" => return api_comments(post_id, show_emails).await,
             "userreaction" => return api_userreaction(post_id, email).await,
+            "reactions" => return api_reactions().await,
+            "me" => return api_me(email).await,
+            "notifications" => {
+                let unread_only: bool = find_query(&queries, "unread")
+                    .is_some_and(|unread| unread == "1" || unread == "true");
+                return api_notifications(email, unread_only).await;
+            }
+            "reactors" => {
+                let target: Option<Target> = match find_query(&queries, "comment_id") {
+                    Some(comment_id) => comment_id.parse::<i64>().ok().map(Target::Comment),
+                    None => Some(Target::Post(post_id)),
+                };
+                return api_reactors(target, show_emails).await;
+            }
             _ => return "HTTP/1.1 404 NOT FOUND\r\nContent-Length: 0\r\n\r\n".to_string(),
         }
     }
 
-    if !file_or_dir_exists(&requested_endpoint.0, &requested_endpoint.1).await {
-        return get_ascii_content("pages", "404.html").await;
+    if requested_endpoint.0 == "tag" {
+        if !auth {
+            return redirect("/login");
+        }
+        return get_tag_page(&requested_endpoint.1).await;
+    }
+
+    let file: String = format!("{}/{}", requested_endpoint.0, requested_endpoint.1);
+    if let Err(error) = static_files::open(&file).await {
+        return resolve_error(error).await;
     }
 
     if !auth && requested_endpoint.1 != "login.html" {
-        return "HTTP/1.1 301 MOVED PERMANENTLY\r\nLocation: /login\r\nContent-Length: 0\r\n\r\n"
-            .to_string()
-            + "301 Moved Permanently";
+        return redirect("/login");
     }
 
     if auth && requested_endpoint.1 == "login.html" {
-        return "HTTP/1.1 301 MOVED PERMANENTLY\r\nLocation: /\r\nContent-Length: 0\r\n\r\n"
-            .to_string()
-            + "301 Moved Permanently";
+        return redirect("/");
     }
 
     return get_ascii_content(&requested_endpoint.0, &requested_endpoint.1).await;
 }
 
-async fn file_or_dir_exists(directory: &str, file: &str) -> bool {
-    let path: String = format!("public/{}/{}", directory, file);
-    let exists: bool = fs::metadata(path).await.is_ok();
-    return exists;
-}
-
-pub async fn get(mut path: String, headers: Vec<(String, String)>) -> (String, Vec<u8>) {
+pub async fn get(mut path: String, headers: Vec<(String, String)>) -> (String, Body) {
+    let original_path: String = path.clone();
     let mut queries: Vec<(String, String)> = Vec::new();
     if path.contains("?") {
         let path_clone: String = path.clone();
@@ -275,8 +663,11 @@ pub async fn get(mut path: String, headers: Vec<(String, String)>) -> (String, V
         let queries_str: &str = temp[1];
         let queries_str: Vec<&str> = queries_str.split("&").collect();
         for query in queries_str {
-            let query: Vec<&str> = query.split("=").collect();
-            queries.push((query[0].to_string(), query[1].to_string()));
+            let query: Vec<&str> = query.splitn(2, "=").collect();
+            let value: String = urlencoding::decode(&query.get(1).unwrap_or(&"").replace("+", " "))
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            queries.push((query[0].to_string(), value));
         }
     }
 
@@ -294,27 +685,95 @@ pub async fn get(mut path: String, headers: Vec<(String, String)>) -> (String, V
     };
     println!("Sha256 token: {}", sha256_token.cyan());
 
-    let mut requested_endpoint: (String, String) = match_type(&path);
+    let requested_endpoint: (String, String) = match_type(&path);
     println!("Requested endpoint: {}", requested_endpoint.1.red());
 
-    let mut binary_content: Vec<u8> = Vec::new();
-    let _temp: Vec<&str> = requested_endpoint.1.split(".").collect::<Vec<&str>>();
-    let extension: &&str = _temp.last().unwrap();
+    // Folders of the web root show their index.html or, when enabled, the list of their files
+    let directory: Option<Vec<DirEntry>> = if path == "/" || path.starts_with("/api/") {
+        None
+    } else {
+        autoindex::find_directory(&path).await
+    };
+    if directory.is_some() {
+        if !auth_token(sha256_token).await {
+            return (redirect("/login"), Body::empty());
+        }
+        if !path.ends_with('/') {
+            let query: String = match original_path.split_once('?') {
+                Some((_, query)) => format!("?{}", query),
+                None => String::new(),
+            };
+            return (redirect(&format!("{}/{}", path, query)), Body::empty());
+        }
+    }
 
-    let mut contents: String;
-    if ["png", "jpg", "jpeg", "gif", "ico"].contains(&extension) {
-        if &requested_endpoint.0 != "images" {
-            requested_endpoint.0 = "images".to_string();
+    if is_static_file(&requested_endpoint) {
+        // Images are public, the other files need the user to be logged in
+        let public: bool = static_files::mime_type(&requested_endpoint.1).starts_with("image/");
+        if !public && !auth_token(sha256_token).await {
+            return (redirect("/login"), Body::empty());
         }
-        contents = format!(
-            "HTTP/1.1 200 OK\r\nContent-Type: image/{}\r\n\r\n",
-            extension
-        );
-        binary_content = get_utf8_content(&requested_endpoint.0, &requested_endpoint.1).await;
+        return get_static_file(&requested_endpoint, &path, &headers).await;
+    }
+
+    let mut contents: String;
+    // Only the HTML pages of the web root are filled with the data of the user, generated bodies never are
+    let template: bool;
+    if let Some(listing) = directory {
+        template = autoindex::has_index(&listing);
+        contents = if template {
+            get_ascii_content(&path, "index.html").await
+        } else if autoindex::enabled(&listing) {
+            autoindex::render(&listing, &path, &queries, &headers)
+        } else {
+            not_found().await
+        };
+    } else if requested_endpoint.0 == "feed" {
+        template = false;
+        contents = feed::get_feed(&requested_endpoint.1, &queries, &headers).await;
+    } else if requested_endpoint.0 == "post" {
+        template = false;
+        let auth: bool = auth_token(sha256_token).await;
+        contents = match permalink::get_post_page(&requested_endpoint.1, &headers, auth).await {
+            Some(page) => page,
+            None => not_found().await,
+        };
+    } else if requested_endpoint.0 == "avatar" {
+        template = false;
+        contents = match avatar::get_avatar(&requested_endpoint.1, &headers) {
+            Some(avatar) => avatar,
+            None => not_found().await,
+        };
+    } else if requested_endpoint.0 == "user" {
+        template = false;
+        let viewer: JsonValue = get_userdata(sha256_token).await;
+        let page: Option<String> =
+            user_page::get_user_page(&requested_endpoint.1, &headers, viewer["email"].as_str())
+                .await;
+        contents = match page {
+            Some(page) => page,
+            None => not_found().await,
+        };
     } else {
+        template = requested_endpoint.0 != "api" && requested_endpoint.0 != "tag";
         contents = match_plain_content(requested_endpoint.clone(), sha256_token, queries).await;
     }
 
-    contents = check_template(&mut contents, get_userdata(sha256_token).await).await;
-    return (contents, binary_content);
+    if template {
+        // Display names and handles are chosen by the users, so they can't be trusted as HTML.
+        // The email and the token are left out, pages only show the public profile of the user.
+        let mut userdata: JsonValue = get_userdata(sha256_token).await;
+        userdata.remove("email");
+        userdata.remove("token");
+        for (_, value) in userdata.entries_mut() {
+            if value.is_string() {
+                *value = escape_html(&value.to_string()).into();
+            }
+        }
+        contents = check_template(&mut contents, userdata).await;
+    }
+    if requested_endpoint.0 == "api" {
+        contents = cache::revalidate_api(contents, &path, &headers);
+    }
+    return compression::compress_response(contents, &headers);
 }
@@ -0,0 +1,68 @@
+use json::JsonValue;
+use rusqlite::Connection;
+
+use crate::db::dbconn;
+use crate::http::static_files;
+use crate::http::token;
+use crate::profiles::{self, Profile, UpdateError};
+
+async fn patch_me(body: &str, sha256_token: &str) -> String {
+    let decoded: JsonValue = token::get_userdata(sha256_token).await;
+    if decoded["email"].is_null() {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
+    let email: &str = decoded["email"].as_str().unwrap();
+
+    let changes: JsonValue = match json::parse(body.trim_end_matches(['\0', '\n'])) {
+        Ok(changes) if changes.is_object() => changes,
+        _ => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request",
+    };
+
+    let dbconn: Connection = dbconn();
+    let mut profile: Profile = profiles::ensure(&dbconn, email);
+    let previous_avatar: String = profile.avatar.clone();
+    match profiles::update(&dbconn, &mut profile, &changes) {
+        Ok(()) => {}
+        Err(UpdateError::Invalid(error)) => {
+            return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + &error
+        }
+        Err(UpdateError::HandleTaken) => {
+            return "HTTP/1.1 409 CONFLICT\r\n\r\n".to_string() + "The handle is already taken"
+        }
+    }
+    if profile.avatar.is_empty() {
+        if let Some(path) = static_files::upload_path(&previous_avatar) {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+    }
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        profile.to_json().dump()
+    );
+}
+
+pub async fn patch(
+    path: String,
+    headers: Vec<(String, String)>,
+    body: String,
+) -> (String, Vec<u8>) {
+    let sha256_token: &str = match headers
+        .iter()
+        .find(|header: &&(String, String)| header.0 == "Cookie")
+    {
+        Some(header) => header
+            .1
+            .split("token=")
+            .collect::<Vec<&str>>()
+            .last()
+            .unwrap(),
+        None => "",
+    };
+
+    let content: String = match path.as_str() {
+        "/api/users/me" => patch_me(&body, sha256_token).await,
+        _ => "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found",
+    };
+    return (content, Vec::new());
+}
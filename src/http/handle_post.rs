@@ -4,8 +4,17 @@ use json::JsonValue;
 use rusqlite::Connection;
 use sha256::digest;
 
+use crate::bookmarks;
+use crate::comments;
 use crate::db::dbconn;
+use crate::events;
+use crate::follows;
 use crate::http::token;
+use crate::mentions;
+use crate::notifications;
+use crate::profiles::{self, Profile};
+use crate::publishing;
+use crate::reactions::{self, Target};
 
 fn post_logout() -> String {
     return format!("HTTP/1.1 301 OK\r\nSet-Cookie: token=; Max-Age=0; Path=/\r\nLocation: /\r\nContent-Length: 0\r\n\r\n");
@@ -15,7 +24,7 @@ async fn post_login(dbconn: Connection, params: Vec<&str>) -> String {
     let mut email: &str = "";
     let mut password: &str = "";
     for param in params {
-        let key_value: Vec<&str> = param.split('=').collect();
+        let key_value: Vec<&str> = param.splitn(2, '=').collect();
         if key_value.len() == 2 {
             let key: &str = key_value[0].trim();
             let value: &str = key_value[1].trim();
@@ -54,6 +63,7 @@ async fn post_login(dbconn: Connection, params: Vec<&str>) -> String {
                 &[&email, &digest(password).as_str()],
             )
             .unwrap();
+        profiles::ensure(&dbconn, email);
     } else {
         let user: String = dbconn
             .query_row(
@@ -93,12 +103,9 @@ async fn post_login(dbconn: Connection, params: Vec<&str>) -> String {
 async fn post_comment(params: Vec<&str>, sha256_token: &str) -> String {
     let mut vec_params: Vec<(String, String)> = Vec::new();
     for param in params {
-        let key_value: Vec<&str> = param.split('=').collect();
+        let key_value: Vec<&str> = param.splitn(2, '=').collect();
         if key_value.len() == 2 {
-            let key: String = key_value[0].to_string();
-            let mut value: String = key_value[1].to_string();
-            value = value.replace("+", " ");
-            vec_params.push((key, value));
+            vec_params.push((key_value[0].to_string(), key_value[1].to_string()));
         }
     }
     let decoded: JsonValue = token::get_userdata(sha256_token).await;
@@ -119,92 +126,358 @@ async fn post_comment(params: Vec<&str>, sha256_token: &str) -> String {
         return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request";
     }
 
-    let content: String = urlencoding::encode(content).to_string();
+    let content: &str = content.trim_end_matches('\n');
     let post_id: i64 = match vec_params.iter().find(|param| param.0 == "post_id") {
-        Some(param) => param.1.parse::<i64>().unwrap(),
+        Some(param) => match param.1.trim().parse::<i64>() {
+            Ok(post_id) => post_id,
+            Err(_) => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request",
+        },
         None => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request",
     };
     let dbconn: Connection = dbconn();
+    if !comments::can_comment(&dbconn, post_id, email) {
+        return "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found";
+    }
+    let content_html: String = mentions::render(&dbconn, content);
+
+    let parent_comment_id: Option<i64> = match vec_params
+        .iter()
+        .find(|param| param.0 == "parent_comment_id" && !param.1.trim().is_empty())
+    {
+        Some(param) => match param.1.trim().parse::<i64>() {
+            Ok(parent_comment_id) => Some(parent_comment_id),
+            Err(_) => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request",
+        },
+        None => None,
+    };
+    if let Some(parent_comment_id) = parent_comment_id {
+        match comments::depth(&dbconn, post_id, parent_comment_id) {
+            Some(depth) if depth < comments::MAX_DEPTH => {}
+            Some(_) => {
+                return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string()
+                    + "Maximum reply depth reached"
+            }
+            None => return "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found",
+        }
+    }
+
     dbconn
         .execute(
-            "INSERT INTO comments (email, content, post_id) VALUES (?1, ?2, ?3)",
-            &[
+            "INSERT INTO comments (email, content, content_html, post_id, parent_comment_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+            [
                 &email as &dyn rusqlite::ToSql,
                 &content as &dyn rusqlite::ToSql,
+                &content_html as &dyn rusqlite::ToSql,
                 &post_id as &dyn rusqlite::ToSql,
+                &parent_comment_id as &dyn rusqlite::ToSql,
             ],
         )
         .unwrap();
+    let comment_id: i64 = dbconn.last_insert_rowid();
+    mentions::save(&dbconn, content, post_id, Some(comment_id));
+    // The comments of a draft are only seen by its author, nobody is notified of them
+    if publishing::is_published(&dbconn, post_id) {
+        notifications::commented(&dbconn, comment_id, email);
+        events::emit(
+            events::COMMENT,
+            json::object! {
+                post_id: post_id,
+                comment_id: comment_id,
+                comments: comments::tree(&dbconn, post_id, false)
+            },
+        );
+    }
 
     return "HTTP/1.1 301 Moved Permanently\r\nLocation: /\r\nContent-Length: 0\r\n\r\n"
         .to_string();
 }
 
+fn reaction_params(params: &[&str]) -> (Option<Target>, String) {
+    let mut target: Option<Target> = None;
+    let mut reaction: String = String::new();
+    for param in params {
+        let key_value: Vec<&str> = param.trim_end_matches('\n').splitn(2, '=').collect();
+        if key_value.len() == 2 {
+            match key_value[0] {
+                "post_id" => target = key_value[1].parse::<i64>().ok().map(Target::Post),
+                "comment_id" => target = key_value[1].parse::<i64>().ok().map(Target::Comment),
+                "reaction" => reaction = key_value[1].to_string(),
+                _ => (),
+            }
+        }
+    }
+    return (target, reaction);
+}
+
+fn emit_reactions(dbconn: &Connection, target: &Target) {
+    let (target_name, id): (&str, i64) = match target {
+        Target::Post(post_id) => ("post", *post_id),
+        Target::Comment(comment_id) => ("comment", *comment_id),
+    };
+    events::emit(
+        events::REACTION,
+        json::object! {
+            target: target_name,
+            id: id,
+            reactions: reactions::counts(dbconn, target)
+        },
+    );
+}
+
 async fn post_reaction(params: Vec<&str>, sha256_token: &str) -> String {
     println!("Params: {:?}, Token: {}", params, sha256_token);
     let decoded: JsonValue = token::get_userdata(sha256_token).await;
-    if decoded == JsonValue::Null {
+    if decoded["email"].is_null() {
         return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
     }
     let email: &str = decoded["email"].as_str().unwrap();
-    let dbconn: Connection = dbconn();
-    let post_id: i64 = match params.iter().find(|param| param.starts_with("post_id=")) {
-        Some(param) => param.split('=').collect::<Vec<&str>>()[1]
-            .trim_end_matches('\n')
-            .parse::<i64>()
-            .unwrap(),
+
+    let (target, reaction): (Option<Target>, String) = reaction_params(&params);
+    let target: Target = match target {
+        Some(target) => target,
         None => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request",
     };
-    let reaction: &str = match params.iter().find(|param| param.starts_with("reaction=")) {
-        Some(param) => param.split('=').collect::<Vec<&str>>()[1],
+    if !reactions::is_allowed(&reaction) {
+        return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "Unknown reaction";
+    }
+
+    let dbconn: Connection = dbconn();
+    if !reactions::exists(&dbconn, &target) {
+        return "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found";
+    }
+    let reaction: Option<String> = reactions::toggle(&dbconn, &target, email, &reaction);
+    notifications::reacted(&dbconn, &target, email, reaction.as_deref());
+    emit_reactions(&dbconn, &target);
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        json::object! {
+            status: "ok",
+            reaction: reaction,
+            reactions: reactions::counts(&dbconn, &target)
+        }
+        .dump()
+    );
+}
+
+async fn post_reaction_remove(params: Vec<&str>, sha256_token: &str) -> String {
+    let decoded: JsonValue = token::get_userdata(sha256_token).await;
+    if decoded["email"].is_null() {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
+    let email: &str = decoded["email"].as_str().unwrap();
+
+    let target: Target = match reaction_params(&params).0 {
+        Some(target) => target,
         None => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request",
     };
-    let reaction: &str = reaction.trim_end_matches('\n');
-    let reaction_exists: bool = dbconn
-        .query_row(
-            "SELECT EXISTS(SELECT 1 FROM reactions WHERE email = ?1 AND post_id = ?2)",
-            &[
-                &email as &dyn rusqlite::ToSql,
-                &post_id as &dyn rusqlite::ToSql,
-            ],
-            |row| row.get(0),
-        )
-        .unwrap();
 
-    if reaction_exists {
-        dbconn
-            .execute(
-                "UPDATE reactions SET type = ?1 WHERE email = ?2 AND post_id = ?3",
-                &[
-                    &reaction as &dyn rusqlite::ToSql,
-                    &email as &dyn rusqlite::ToSql,
-                    &post_id as &dyn rusqlite::ToSql,
-                ],
-            )
-            .unwrap();
-        return "HTTP/1.1 200 OK\r\n\r\n {\"status\": \"ok\"}".to_string();
+    let dbconn: Connection = dbconn();
+    if reactions::remove(&dbconn, &target, email) {
+        notifications::reacted(&dbconn, &target, email, None);
+        emit_reactions(&dbconn, &target);
     }
 
-    dbconn
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        json::object! {
+            status: "ok",
+            reaction: JsonValue::Null,
+            reactions: reactions::counts(&dbconn, &target)
+        }
+        .dump()
+    );
+}
+
+async fn post_publish(params: Vec<&str>, sha256_token: &str) -> String {
+    let decoded: JsonValue = token::get_userdata(sha256_token).await;
+    if decoded["email"].is_null() {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
+    let email: &str = decoded["email"].as_str().unwrap();
+
+    let mut post_id: Option<i64> = None;
+    let mut status: &str = publishing::PUBLISHED;
+    let mut publish_at: &str = "";
+    for param in params {
+        let key_value: Vec<&str> = param.trim_end_matches('\n').splitn(2, '=').collect();
+        if key_value.len() == 2 {
+            match key_value[0] {
+                "post_id" => post_id = key_value[1].parse::<i64>().ok(),
+                "status" => status = key_value[1],
+                "publish_at" => publish_at = key_value[1],
+                _ => (),
+            }
+        }
+    }
+    let post_id: i64 = match post_id {
+        Some(post_id) => post_id,
+        None => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request",
+    };
+
+    let (status, publish_at): (String, Option<String>) =
+        match publishing::resolve_status(status, publish_at) {
+            Ok(resolved) => resolved,
+            Err(error) => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + &error,
+        };
+
+    let dbconn: Connection = dbconn();
+    let updated: usize = dbconn
         .execute(
-            "INSERT INTO reactions (email, post_id, type) VALUES (?1, ?2, ?3)",
-            &[
-                &email as &dyn rusqlite::ToSql,
+            "UPDATE posts SET status = ?1, publish_at = CASE WHEN ?1 = 'draft' THEN NULL ELSE COALESCE(?2, CURRENT_TIMESTAMP) END
+                WHERE post_id = ?3 AND email = ?4 AND status != 'published'",
+            [
+                &status as &dyn rusqlite::ToSql,
+                &publish_at as &dyn rusqlite::ToSql,
                 &post_id as &dyn rusqlite::ToSql,
-                &reaction as &dyn rusqlite::ToSql,
+                &email as &dyn rusqlite::ToSql,
             ],
         )
         .unwrap();
+    if updated == 0 {
+        return "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found";
+    }
+
+    if status == publishing::PUBLISHED {
+        publishing::published(&dbconn, post_id);
+    }
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        json::object! { status: status, publish_at: publish_at }.dump()
+    );
+}
+
+async fn post_notifications_read(params: Vec<&str>, sha256_token: &str) -> String {
+    let decoded: JsonValue = token::get_userdata(sha256_token).await;
+    if decoded["email"].is_null() {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
+    let email: &str = decoded["email"].as_str().unwrap();
+
+    // Without a notification_id every notification of the user is marked as read
+    let mut notification_id: Option<i64> = None;
+    for param in params {
+        let key_value: Vec<&str> = param.trim_end_matches('\n').splitn(2, '=').collect();
+        if key_value.len() == 2 && key_value[0] == "notification_id" {
+            match key_value[1].parse::<i64>() {
+                Ok(id) => notification_id = Some(id),
+                Err(_) => {
+                    return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request"
+                }
+            }
+        }
+    }
+
+    let dbconn: Connection = dbconn();
+    notifications::mark_read(&dbconn, email, notification_id);
 
-    return "HTTP/1.1 200 OK\r\n\r\n {\"status\": \"ok\"}".to_string();
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        json::object! { status: "ok", unread: notifications::unread_count(&dbconn, email) }.dump()
+    );
+}
+
+async fn post_notifications_digest(params: Vec<&str>, sha256_token: &str) -> String {
+    let decoded: JsonValue = token::get_userdata(sha256_token).await;
+    if decoded["email"].is_null() {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
+    let email: &str = decoded["email"].as_str().unwrap();
+
+    let mut enabled: Option<bool> = None;
+    for param in params {
+        let key_value: Vec<&str> = param.trim_end_matches('\n').splitn(2, '=').collect();
+        if key_value.len() == 2 && key_value[0] == "enabled" {
+            enabled = match key_value[1] {
+                "true" | "1" | "on" => Some(true),
+                "false" | "0" | "off" => Some(false),
+                _ => None,
+            };
+        }
+    }
+    let enabled: bool = match enabled {
+        Some(enabled) => enabled,
+        None => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "400 Bad Request",
+    };
+
+    notifications::set_digest(&dbconn(), email, enabled);
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        json::object! { status: "ok", digest: enabled }.dump()
+    );
+}
+
+async fn post_follow(handle: &str, sha256_token: &str) -> String {
+    let decoded: JsonValue = token::get_userdata(sha256_token).await;
+    if decoded["email"].is_null() {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
+    let email: &str = decoded["email"].as_str().unwrap();
+
+    let dbconn: Connection = dbconn();
+    let author: Profile = match profiles::by_handle(&dbconn, handle) {
+        Some(author) => author,
+        None => return "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found",
+    };
+    if author.email == email {
+        return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "You can't follow yourself";
+    }
+    follows::follow(&dbconn, email, &author.email);
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        json::object! {
+            status: "ok",
+            following: true,
+            followers: follows::counts(&dbconn, &author.email)["followers"].clone()
+        }
+        .dump()
+    );
+}
+
+async fn post_bookmark(post_id: &str, sha256_token: &str) -> String {
+    let decoded: JsonValue = token::get_userdata(sha256_token).await;
+    if decoded["email"].is_null() {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 Unauthorized";
+    }
+    let email: &str = decoded["email"].as_str().unwrap();
+
+    let dbconn: Connection = dbconn();
+    let post_id: i64 = match post_id.parse::<i64>() {
+        // Only published posts can be bookmarked
+        Ok(post_id) if publishing::is_published(&dbconn, post_id) => post_id,
+        _ => return "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found",
+    };
+    bookmarks::add(&dbconn, email, post_id);
+
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        json::object! { status: "ok", bookmarked: true }.dump()
+    );
+}
+
+// Form values encode spaces as "+" and everything else as %XX
+fn decode_form(value: &str) -> String {
+    let value: String = value.replace("+", " ");
+    return urlencoding::decode(&value)
+        .map(|decoded| decoded.to_string())
+        .unwrap_or(value);
 }
 
 pub async fn post(path: String, headers: Vec<(String, String)>, body: String) -> (String, Vec<u8>) {
-    let body: String = urlencoding::decode(body.as_str()).unwrap().to_string();
-    let params: Vec<&str> = body
+    // The body is split before decoding, so an encoded "&" or "=" stays in its value
+    let params: Vec<String> = body
+        .trim_end_matches('\n')
         .split("&")
         .map(|param: &str| param.trim_end_matches('\0'))
+        .map(|param: &str| match param.split_once('=') {
+            Some((key, value)) => format!("{}={}", decode_form(key), decode_form(value)),
+            None => decode_form(param),
+        })
         .collect();
+    let params: Vec<&str> = params.iter().map(|param: &String| param.as_str()).collect();
     let sha256_token: &str = match headers
         .iter()
         .find(|header: &&(String, String)| header.0 == "Cookie")
@@ -224,6 +497,22 @@ pub async fn post(path: String, headers: Vec<(String, String)>, body: String) ->
         "/api/logout" => post_logout(),
         "/api/comment" => post_comment(params, sha256_token).await,
         "/api/reaction" => post_reaction(params, sha256_token).await,
+        "/api/reaction/remove" => post_reaction_remove(params, sha256_token).await,
+        "/api/publish" => post_publish(params, sha256_token).await,
+        "/api/notifications/read" => post_notifications_read(params, sha256_token).await,
+        "/api/notifications/digest" => post_notifications_digest(params, sha256_token).await,
+        _ if path.starts_with("/api/users/") && path.ends_with("/follow") => {
+            let handle: &str = path
+                .trim_start_matches("/api/users/")
+                .trim_end_matches("/follow");
+            post_follow(handle, sha256_token).await
+        }
+        _ if path.starts_with("/api/posts/") && path.ends_with("/bookmark") => {
+            let post_id: &str = path
+                .trim_start_matches("/api/posts/")
+                .trim_end_matches("/bookmark");
+            post_bookmark(post_id, sha256_token).await
+        }
         _ => "HTTP/1.1 404 NOT FOUND\r\n\r\n".to_string() + "404 Not Found",
     };
 
@@ -1,3 +1,19 @@
+pub mod autoindex;
+pub mod avatar;
+pub mod body;
+pub mod cache;
+pub mod compression;
+pub mod date;
+pub mod feed;
+pub mod handle_delete;
 pub mod handle_get;
+pub mod handle_patch;
 pub mod handle_post;
+pub mod permalink;
+pub mod range;
+pub mod request;
+pub mod sse;
+pub mod static_files;
 pub mod token;
+pub mod user_page;
+pub mod websocket;
@@ -0,0 +1,159 @@
+use json::JsonValue;
+use rusqlite::Connection;
+
+use crate::comments;
+use crate::config;
+use crate::db::dbconn;
+use crate::http::date;
+use crate::http::handle_get::check_template;
+use crate::http::static_files;
+use crate::markdown::{escape_html, plain_text};
+use crate::profiles::{self, Profile};
+use crate::reactions::{self, Target};
+use crate::tags;
+
+// Characters of the content used in the description meta tags
+pub const DESCRIPTION_LENGTH: usize = 200;
+
+fn render_reactions(counts: &JsonValue, size: &str) -> String {
+    let mut reactions: String = String::new();
+    for kind in reactions::kinds() {
+        reactions.push_str(&format!(
+            r#"<div class="btn {} no-animation" title="{}">{}<div class="badge badge-secondary">{}</div></div>"#,
+            size,
+            escape_html(&kind.label),
+            escape_html(&kind.emoji),
+            counts[kind.name.as_str()].as_i64().unwrap_or(0)
+        ));
+    }
+    return reactions;
+}
+
+fn render_thread(comments: &JsonValue, post_id: i64, auth: bool) -> String {
+    let mut thread: String = String::new();
+    for comment in comments.members() {
+        let depth: usize = comment["depth"].as_usize().unwrap_or(0);
+        let reply_form: String = if auth && depth < comments::MAX_DEPTH {
+            format!(
+                r#"<details class="text-sm"><summary class="cursor-pointer text-gray-500">Reply</summary><form action="/api/comment" method="POST" class="flex gap-2 mt-2"><input type="hidden" name="post_id" value="{}" /><input type="hidden" name="parent_comment_id" value="{}" /><input class="input input-bordered input-sm grow" name="content" placeholder="Reply" required /><button class="btn btn-sm btn-primary">Reply</button></form></details>"#,
+                post_id, comment["comment_id"]
+            )
+        } else {
+            String::new()
+        };
+        thread.push_str(&format!(
+            r#"<div class="flex flex-col gap-1" id="comment-{}"><div class="flex justify-between items-center"><a class="font-bold link link-hover" href="/u/{}">{}</a><span class="text-xs text-gray-500">{}</span></div><div class="prose prose-sm max-w-none break-words">{}</div><div class="flex gap-2">{}</div>{}<div class="flex flex-col gap-4 pl-4 border-l-2 border-base-300">{}</div></div>"#,
+            comment["comment_id"],
+            escape_html(&comment["author"]["handle"].to_string()),
+            escape_html(&comment["author"]["display_name"].to_string()),
+            escape_html(&comment["datetime"].to_string()),
+            comment["content_html"],
+            render_reactions(&comment["reactions"], "btn-xs"),
+            reply_form,
+            render_thread(&comment["replies"], post_id, auth)
+        ));
+    }
+    return thread;
+}
+
+fn render_comments(dbconn: &Connection, post_id: i64, auth: bool) -> String {
+    let comments: String = render_thread(&comments::tree(dbconn, post_id, false), post_id, auth);
+    if comments.is_empty() {
+        return r#"<div class="text-center text-gray-500">No comments yet</div>"#.to_string();
+    }
+    return comments;
+}
+
+fn render_comment_form(post_id: i64, auth: bool) -> String {
+    if !auth {
+        return r#"<a href="/login" class="btn btn-primary">Login to comment</a>"#.to_string();
+    }
+    return format!(
+        r#"<form action="/api/comment" method="POST" class="card-actions justify-end mt-6"><input type="hidden" name="post_id" value="{}" /><input class="input input-bordered grow" name="content" placeholder="Add a comment" required /><button class="btn btn-primary grow lg:grow-0">Comment</button></form>"#,
+        post_id
+    );
+}
+
+pub async fn get_post_page(
+    post_id: &str,
+    headers: &[(String, String)],
+    auth: bool,
+) -> Option<String> {
+    let post_id: i64 = post_id.parse::<i64>().ok()?;
+    let dbconn: Connection = dbconn();
+    let post: (String, String, String, String, String, String) = dbconn
+        .query_row(
+            "SELECT title, content, content_html, email, publish_at, image FROM posts
+                WHERE post_id = ?1 AND status = 'published' AND publish_at <= CURRENT_TIMESTAMP",
+            [post_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                    row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            },
+        )
+        .ok()?;
+    let (title, content, content_html, email, publish_at, image) = post;
+    let author: Profile = profiles::ensure(&dbconn, &email);
+
+    let base_url: String = config::public_url(headers);
+    let canonical: String = format!("{}/post/{}", base_url, post_id);
+    let published: i64 = date::parse_sql(&publish_at).unwrap_or(0);
+
+    let (image_html, image_meta, twitter_card): (String, String, &str) = if image.is_empty() {
+        (String::new(), String::new(), "summary")
+    } else {
+        let image_url: String = escape_html(&format!("{}{}", base_url, image));
+        (
+            format!(
+                r#"<figure class="px-10 pt-10"><img src="{}" alt="{}" class="rounded-xl" /></figure>"#,
+                escape_html(&image),
+                escape_html(&title)
+            ),
+            format!(
+                r#"<meta property="og:image" content="{}" /><meta name="twitter:image" content="{}" />"#,
+                image_url, image_url
+            ),
+            "summary_large_image",
+        )
+    };
+
+    let mut tags_html: String = String::new();
+    for tag in tags::of_post(&dbconn, post_id).members() {
+        let tag: String = escape_html(&tag.to_string());
+        tags_html.push_str(&format!(
+            r#"<a class="badge badge-outline" href="/tag/{}">#{}</a>"#,
+            tag, tag
+        ));
+    }
+
+    let values: JsonValue = json::object! {
+        title: escape_html(&title),
+        description: escape_html(&plain_text(&content, DESCRIPTION_LENGTH)),
+        canonical: escape_html(&canonical),
+        published_time: date::to_rfc3339(published),
+        image_meta: image_meta,
+        twitter_card: twitter_card,
+        author: escape_html(&author.name()),
+        author_handle: escape_html(&author.handle),
+        datetime: escape_html(&format!("{} UTC", publish_at)),
+        image_html: image_html,
+        tags_html: tags_html,
+        content_html: content_html,
+        reactions_html: render_reactions(&reactions::counts(&dbconn, &Target::Post(post_id)), "btn-sm"),
+        comments_html: render_comments(&dbconn, post_id, auth),
+        comment_form: render_comment_form(post_id, auth)
+    };
+
+    let mut contents: String = static_files::read_to_string("pages/post.html").await.ok()?;
+    let contents: String = check_template(&mut contents, values).await;
+    return Some(format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n{}",
+        contents
+    ));
+}
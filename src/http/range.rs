@@ -0,0 +1,275 @@
+use uuid::Uuid;
+
+use crate::http::body::{Body, FilePart};
+use crate::http::date;
+use crate::http::request::find_header;
+use crate::http::static_files::Source;
+
+// Requests with more ranges than this get the whole file, so a single request can't ask for thousands of tiny parts
+const MAX_RANGES: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum Ranges {
+    // No Range header, an invalid one or an If-Range that doesn't match, the whole file is sent
+    Full,
+    // Inclusive (first, last) byte positions, sorted and merged when they overlap
+    Partial(Vec<(u64, u64)>),
+    // Every range starts after the end of the file
+    Unsatisfiable,
+}
+
+// If-Range only allows a partial response when the file hasn't changed since the client got it,
+// ETags use the strong comparison so a weak one never matches
+fn if_range_matches(if_range: &str, etag: &str, last_modified: i64) -> bool {
+    let if_range: &str = if_range.trim();
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return if_range == etag;
+    }
+    return date::parse_http(if_range) == Some(last_modified);
+}
+
+// A "first-last", "first-" or "-suffix" range, None if it is invalid
+// and Some(None) if it is valid but outside of the file
+fn parse_range(range: &str, length: u64) -> Option<Option<(u64, u64)>> {
+    let (first, last) = range.trim().split_once('-')?;
+    let (first, last): (&str, &str) = (first.trim(), last.trim());
+    if first.is_empty() {
+        let suffix: u64 = last.parse::<u64>().ok()?;
+        if suffix == 0 || length == 0 {
+            return Some(None);
+        }
+        return Some(Some((length.saturating_sub(suffix), length - 1)));
+    }
+
+    let first: u64 = first.parse::<u64>().ok()?;
+    let last: u64 = if last.is_empty() {
+        u64::MAX
+    } else {
+        last.parse::<u64>().ok()?
+    };
+    if last < first {
+        return None;
+    }
+    if first >= length {
+        return Some(None);
+    }
+    return Some(Some((first, last.min(length - 1))));
+}
+
+// Ranges of the file requested with the Range header (RFC 9110 14.2)
+pub fn parse(headers: &[(String, String)], length: u64, etag: &str, last_modified: i64) -> Ranges {
+    let range: &str = match find_header(headers, "Range") {
+        Some(range) => range,
+        None => return Ranges::Full,
+    };
+    if let Some(if_range) = find_header(headers, "If-Range") {
+        if !if_range_matches(if_range, etag, last_modified) {
+            return Ranges::Full;
+        }
+    }
+    let ranges: &str = match range.trim().strip_prefix("bytes=") {
+        Some(ranges) => ranges,
+        None => return Ranges::Full,
+    };
+    if ranges.split(',').count() > MAX_RANGES {
+        return Ranges::Full;
+    }
+
+    let mut satisfiable: Vec<(u64, u64)> = Vec::new();
+    for range in ranges.split(',') {
+        // Lists can have empty elements (RFC 9110 5.6.1)
+        if range.trim().is_empty() {
+            continue;
+        }
+        match parse_range(range, length) {
+            Some(Some(range)) => satisfiable.push(range),
+            Some(None) => {}
+            None => return Ranges::Full,
+        }
+    }
+    if satisfiable.is_empty() {
+        return Ranges::Unsatisfiable;
+    }
+
+    satisfiable.sort();
+    let mut merged: Vec<(u64, u64)> = Vec::new();
+    for (first, last) in satisfiable {
+        match merged.last_mut() {
+            Some(previous) if first <= previous.1.saturating_add(1) => {
+                previous.1 = previous.1.max(last);
+            }
+            _ => merged.push((first, last)),
+        }
+    }
+    return Ranges::Partial(merged);
+}
+
+// multipart/byteranges body with a part for each range, returned with its boundary
+pub fn byteranges(
+    source: Source,
+    ranges: &[(u64, u64)],
+    length: u64,
+    content_type: &str,
+) -> (String, Body) {
+    let boundary: String = Uuid::new_v4().simple().to_string();
+    let parts: Vec<FilePart> = ranges
+        .iter()
+        .map(|(first, last)| FilePart {
+            head: format!(
+                "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                boundary, content_type, first, last, length
+            ),
+            first: *first,
+            length: last - first + 1,
+        })
+        .collect();
+    let trailer: String = format!("\r\n--{}--\r\n", boundary);
+    return match source {
+        Source::Disk(file) => (boundary, Body::Multipart(file, parts, trailer)),
+        // The parts of an embedded file are already in memory
+        Source::Embedded(bytes) => {
+            let mut body: Vec<u8> = Vec::new();
+            for part in parts {
+                body.extend_from_slice(part.head.as_bytes());
+                body.extend_from_slice(
+                    &bytes[part.first as usize..(part.first + part.length) as usize],
+                );
+            }
+            body.extend_from_slice(trailer.as_bytes());
+            (boundary, Body::Bytes(body))
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETAG: &str = "\"5f-64\"";
+    const MODIFIED: i64 = 1_700_000_000;
+
+    fn ranges(headers: &[(&str, &str)]) -> Ranges {
+        let headers: Vec<(String, String)> = headers
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        return parse(&headers, 100, ETAG, MODIFIED);
+    }
+
+    #[test]
+    fn single_ranges() {
+        assert_eq!(
+            ranges(&[("Range", "bytes=0-9")]),
+            Ranges::Partial(vec![(0, 9)])
+        );
+        assert_eq!(
+            ranges(&[("range", "bytes=90-")]),
+            Ranges::Partial(vec![(90, 99)])
+        );
+        assert_eq!(
+            ranges(&[("Range", "bytes=-10")]),
+            Ranges::Partial(vec![(90, 99)])
+        );
+        assert_eq!(
+            ranges(&[("Range", "bytes=-500")]),
+            Ranges::Partial(vec![(0, 99)])
+        );
+        assert_eq!(
+            ranges(&[("Range", "bytes=50-500")]),
+            Ranges::Partial(vec![(50, 99)])
+        );
+    }
+
+    #[test]
+    fn invalid_ranges_get_the_whole_file() {
+        assert_eq!(ranges(&[]), Ranges::Full);
+        assert_eq!(ranges(&[("Range", "items=0-9")]), Ranges::Full);
+        assert_eq!(ranges(&[("Range", "bytes=9-0")]), Ranges::Full);
+        assert_eq!(ranges(&[("Range", "bytes=a-b")]), Ranges::Full);
+        assert_eq!(ranges(&[("Range", "bytes=0-9,x")]), Ranges::Full);
+    }
+
+    #[test]
+    fn unsatisfiable_ranges() {
+        assert_eq!(ranges(&[("Range", "bytes=100-")]), Ranges::Unsatisfiable);
+        assert_eq!(ranges(&[("Range", "bytes=-0")]), Ranges::Unsatisfiable);
+        assert_eq!(
+            ranges(&[("Range", "bytes=200-300, 150-")]),
+            Ranges::Unsatisfiable
+        );
+        // The satisfiable ones are still sent
+        assert_eq!(
+            ranges(&[("Range", "bytes=200-300,0-0")]),
+            Ranges::Partial(vec![(0, 0)])
+        );
+    }
+
+    #[test]
+    fn ranges_are_sorted_and_merged() {
+        assert_eq!(
+            ranges(&[("Range", "bytes=50-59, 0-9")]),
+            Ranges::Partial(vec![(0, 9), (50, 59)])
+        );
+        assert_eq!(
+            ranges(&[("Range", "bytes=0-9,5-19,20-29")]),
+            Ranges::Partial(vec![(0, 29)])
+        );
+        assert_eq!(
+            ranges(&[("Range", "bytes=0-9,,-5")]),
+            Ranges::Partial(vec![(0, 9), (95, 99)])
+        );
+    }
+
+    #[test]
+    fn too_many_ranges_get_the_whole_file() {
+        let many: String = format!("bytes={}", vec!["0-0"; MAX_RANGES + 1].join(","));
+        assert_eq!(ranges(&[("Range", &many)]), Ranges::Full);
+        let allowed: String = format!("bytes={}", vec!["0-0"; MAX_RANGES].join(","));
+        assert_eq!(
+            ranges(&[("Range", &allowed)]),
+            Ranges::Partial(vec![(0, 0)])
+        );
+    }
+
+    #[test]
+    fn if_range() {
+        let date: String = date::to_http(MODIFIED);
+        let old_date: String = date::to_http(MODIFIED - 1);
+        assert_eq!(
+            ranges(&[("Range", "bytes=0-9"), ("If-Range", ETAG)]),
+            Ranges::Partial(vec![(0, 9)])
+        );
+        assert_eq!(
+            ranges(&[("Range", "bytes=0-9"), ("If-Range", &date)]),
+            Ranges::Partial(vec![(0, 9)])
+        );
+        assert_eq!(
+            ranges(&[("Range", "bytes=0-9"), ("If-Range", "\"other\"")]),
+            Ranges::Full
+        );
+        assert_eq!(
+            ranges(&[("Range", "bytes=0-9"), ("If-Range", "W/\"5f-64\"")]),
+            Ranges::Full
+        );
+        assert_eq!(
+            ranges(&[("Range", "bytes=0-9"), ("If-Range", &old_date)]),
+            Ranges::Full
+        );
+    }
+
+    #[test]
+    fn embedded_byteranges() {
+        static DATA: [u8; 10] = *b"0123456789";
+        let (boundary, body): (String, Body) =
+            byteranges(Source::Embedded(&DATA), &[(0, 1), (8, 9)], 10, "text/plain");
+        let expected: String = format!(
+            "\r\n--{0}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/10\r\n\r\n01\r\n--{0}\r\nContent-Type: text/plain\r\nContent-Range: bytes 8-9/10\r\n\r\n89\r\n--{0}--\r\n",
+            boundary
+        );
+        assert_eq!(body.length(), expected.len() as u64);
+        match body {
+            Body::Bytes(bytes) => assert_eq!(bytes, expected.as_bytes()),
+            _ => panic!("embedded files are sent from memory"),
+        }
+    }
+}
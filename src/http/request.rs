@@ -0,0 +1,15 @@
+// Header names are case-insensitive, the first header with the name is used
+pub fn find_header<'a>(headers: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    return headers
+        .iter()
+        .find(|header: &&(String, String)| header.0.eq_ignore_ascii_case(key))
+        .map(|header: &(String, String)| header.1.as_str());
+}
+
+// Value of a query parameter, already percent-decoded when the path is parsed
+pub fn find_query<'a>(queries: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    return queries
+        .iter()
+        .find(|query: &&(String, String)| query.0 == key)
+        .map(|query: &(String, String)| query.1.as_str());
+}
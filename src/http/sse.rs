@@ -0,0 +1,102 @@
+use colored::Colorize;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::{self, Event};
+use crate::http::request::find_header;
+use crate::http::token::auth_token;
+
+const PATH: &str = "/api/events";
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+// Milliseconds the browser waits before reconnecting
+const RETRY: u64 = 3000;
+
+pub fn is_stream(method: &str, path: &str) -> bool {
+    return method == "GET" && path.split('?').next() == Some(PATH);
+}
+
+fn format_event(event: &Event) -> String {
+    return format!(
+        "id: {}\nevent: {}\ndata: {}\n\n",
+        event.id, event.name, event.data
+    );
+}
+
+// Last-Event-ID is sent by the browser when it reconnects, the query is for clients that can't set headers
+fn last_event_id(path: &str, headers: &[(String, String)]) -> Option<u64> {
+    let header: Option<&str> = find_header(headers, "Last-Event-ID");
+    let query: Option<&str> = path.split_once('?').and_then(|(_, queries)| {
+        queries
+            .split('&')
+            .find_map(|query: &str| query.strip_prefix("last_event_id="))
+    });
+    return header.or(query)?.trim().parse::<u64>().ok();
+}
+
+pub async fn handle(mut socket: TcpStream, path: &str, headers: &[(String, String)]) {
+    // Same session check as the WebSocket handshake
+    let sha256_token: &str = match find_header(headers, "Cookie") {
+        Some(cookie) => cookie.split("token=").last().unwrap(),
+        None => "",
+    };
+    if !auth_token(sha256_token).await {
+        let _ = socket
+            .write_all(
+                "HTTP/1.1 401 UNAUTHORIZED\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .as_bytes(),
+            )
+            .await;
+        return;
+    }
+
+    let (missed, complete, mut receiver) = match last_event_id(path, headers) {
+        Some(last_id) => events::resume(last_id),
+        None => (Vec::new(), true, events::subscribe()),
+    };
+
+    let mut stream: String = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\nX-Accel-Buffering: no\r\n\r\nretry: {}\n\n",
+        RETRY
+    );
+    // Some events are gone, the client has to reload instead of relying on the stream
+    if !complete {
+        stream.push_str("event: reset\ndata: {}\n\n");
+    }
+    for event in &missed {
+        stream.push_str(&format_event(event));
+    }
+    if socket.write_all(stream.as_bytes()).await.is_err() {
+        return;
+    }
+    println!(
+        "Event stream {} ({} events resent)",
+        "opened".green(),
+        missed.len()
+    );
+
+    let mut chunk: [u8; 1024] = [0; 1024];
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    heartbeat.tick().await;
+
+    loop {
+        let message: String = tokio::select! {
+            // Clients don't send anything, reading only detects the disconnection
+            read = socket.read(&mut chunk) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(_) => continue,
+            },
+            event = receiver.recv() => match event {
+                Ok(event) => format_event(&event),
+                // Closing makes the browser reconnect with Last-Event-ID and catch up from the backlog
+                Err(RecvError::Lagged(_)) | Err(RecvError::Closed) => break,
+            },
+            _ = heartbeat.tick() => ": heartbeat\n\n".to_string(),
+        };
+        if socket.write_all(message.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+    println!("Event stream {}", "closed".red());
+}
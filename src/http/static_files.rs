@@ -0,0 +1,435 @@
+#[cfg(feature = "embed")]
+use include_dir::{include_dir, Dir};
+use std::fs::Metadata;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::config;
+use crate::http::body::Body;
+
+// The public folder as it was at build time, for a deployment with only the executable
+#[cfg(feature = "embed")]
+static PUBLIC: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/public");
+
+// Content type of the static files by extension, the others are sent as application/octet-stream
+const MIME_TYPES: [(&str, &str); 38] = [
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("xml", "application/xml"),
+    ("rss", "application/rss+xml"),
+    ("atom", "application/atom+xml"),
+    ("svg", "image/svg+xml"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("ico", "image/x-icon"),
+    ("bmp", "image/bmp"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("ogv", "video/ogg"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("flac", "audio/flac"),
+];
+
+// Types that aren't text/* but are still text, they are sent with a charset too
+const TEXT_MIME_TYPES: [&str; 6] = [
+    "application/json",
+    "application/manifest+json",
+    "application/xml",
+    "application/rss+xml",
+    "application/atom+xml",
+    "image/svg+xml",
+];
+
+pub fn mime_type(file: &str) -> &'static str {
+    let extension: String = match file.rsplit_once('.') {
+        Some((_, extension)) => extension.to_lowercase(),
+        None => return "application/octet-stream",
+    };
+    return MIME_TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, mime)| *mime)
+        .unwrap_or("application/octet-stream");
+}
+
+// Images that can't carry scripts, SVG is left out
+pub fn is_raster_image(file: &str) -> bool {
+    let mime: &str = mime_type(file);
+    return mime.starts_with("image/") && mime != "image/svg+xml";
+}
+
+// Content-Type header of a static file, text files are always UTF-8
+pub fn content_type(file: &str) -> String {
+    let mime: &str = mime_type(file);
+    if mime.starts_with("text/") || TEXT_MIME_TYPES.contains(&mime) {
+        return format!("{}; charset=utf-8", mime);
+    }
+    return mime.to_string();
+}
+
+pub enum ResolveError {
+    // The path can't be decoded or it climbs above the web root
+    BadRequest,
+    // The file is missing or it is outside the web root once the symlinks are followed
+    NotFound,
+}
+
+// Percent-decodes the request path and removes the "." and ".." segments,
+// a ".." that would go above the web root is rejected
+fn normalize(path: &str) -> Result<PathBuf, ResolveError> {
+    let decoded: String = match urlencoding::decode(path) {
+        Ok(decoded) => decoded.to_string(),
+        Err(_) => return Err(ResolveError::BadRequest),
+    };
+    if decoded.contains('\0') {
+        return Err(ResolveError::BadRequest);
+    }
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(ResolveError::BadRequest);
+                }
+            }
+            _ => segments.push(segment),
+        }
+    }
+    return Ok(segments.iter().collect());
+}
+
+// The static files are read from the web root, always without the embed feature
+fn disk_assets() -> bool {
+    return !cfg!(feature = "embed") || config::dev_assets();
+}
+
+// Canonical path of a file relative to a directory, None when it is missing or outside of it
+async fn resolve_in(root: &str, relative: &Path) -> Option<PathBuf> {
+    let root: PathBuf = fs::canonicalize(root).await.ok()?;
+    let resolved: PathBuf = fs::canonicalize(root.join(relative)).await.ok()?;
+
+    // A symlink inside the directory can still point outside of it
+    if !resolved.starts_with(&root) {
+        return None;
+    }
+    return Some(resolved);
+}
+
+// Path on disk of an uploaded image from its /images/ URL
+pub fn upload_path(url: &str) -> Option<PathBuf> {
+    let name: &str = url.strip_prefix("/images/")?;
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return None;
+    }
+    return Some(Path::new(&config::upload_dir()).join(name));
+}
+
+pub enum Source {
+    Disk(fs::File),
+    // Part of the executable with the embed feature
+    #[cfg_attr(not(feature = "embed"), allow(dead_code))]
+    Embedded(&'static [u8]),
+}
+
+pub struct StaticFile {
+    pub source: Source,
+    pub length: u64,
+    pub modified: Option<SystemTime>,
+    // Comes from the upload directory, so its content was chosen by a user
+    pub uploaded: bool,
+}
+
+impl StaticFile {
+    async fn from_disk(path: PathBuf) -> Option<StaticFile> {
+        let file: fs::File = fs::File::open(path).await.ok()?;
+        let metadata: Metadata = file.metadata().await.ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+        return Some(StaticFile {
+            source: Source::Disk(file),
+            length: metadata.len(),
+            modified: metadata.modified().ok(),
+            uploaded: false,
+        });
+    }
+
+    #[cfg(feature = "embed")]
+    fn from_embedded(relative: &Path) -> Option<StaticFile> {
+        let file: &'static include_dir::File<'static> = PUBLIC.get_file(relative)?;
+        return Some(StaticFile {
+            source: Source::Embedded(file.contents()),
+            length: file.contents().len() as u64,
+            modified: file
+                .metadata()
+                .map(|metadata: &include_dir::Metadata| metadata.modified()),
+            uploaded: false,
+        });
+    }
+
+    #[cfg(not(feature = "embed"))]
+    fn from_embedded(_relative: &Path) -> Option<StaticFile> {
+        return None;
+    }
+
+    // Body with the bytes from first, the file is streamed from where it is read
+    pub async fn body(self, first: u64, length: u64) -> std::io::Result<Body> {
+        return match self.source {
+            Source::Disk(mut file) => {
+                file.seek(SeekFrom::Start(first)).await?;
+                Ok(Body::File(file, length))
+            }
+            Source::Embedded(bytes) => Ok(Body::Static(
+                &bytes[first as usize..(first + length) as usize],
+            )),
+        };
+    }
+
+    pub async fn read(self) -> std::io::Result<Vec<u8>> {
+        return match self.source {
+            Source::Disk(mut file) => {
+                let mut contents: Vec<u8> = Vec::new();
+                file.read_to_end(&mut contents).await?;
+                Ok(contents)
+            }
+            Source::Embedded(bytes) => Ok(bytes.to_vec()),
+        };
+    }
+}
+
+// Opens a file requested relative to the web root. Uploaded images come from the upload directory,
+// then the file is looked for on disk and in the executable when the public folder is embedded.
+pub async fn open(path: &str) -> Result<StaticFile, ResolveError> {
+    let relative: PathBuf = normalize(path)?;
+    if let Ok(image) = relative.strip_prefix("images") {
+        if let Some(uploaded) = resolve_in(&config::upload_dir(), image).await {
+            if let Some(mut file) = StaticFile::from_disk(uploaded).await {
+                // Nothing else should be in there, but a file put by hand is still not served
+                if !is_raster_image(path) {
+                    return Err(ResolveError::NotFound);
+                }
+                file.uploaded = true;
+                return Ok(file);
+            }
+        }
+    }
+    if disk_assets() {
+        if let Some(resolved) = resolve_in(&config::web_root(), &relative).await {
+            if let Some(file) = StaticFile::from_disk(resolved).await {
+                return Ok(file);
+            }
+        }
+    }
+    return StaticFile::from_embedded(&relative).ok_or(ResolveError::NotFound);
+}
+
+// Contents of a page or template of the web root
+pub async fn read_to_string(path: &str) -> Result<String, ResolveError> {
+    let contents: Vec<u8> = open(path)
+        .await?
+        .read()
+        .await
+        .map_err(|_| ResolveError::NotFound)?;
+    return String::from_utf8(contents).map_err(|_| ResolveError::NotFound);
+}
+
+pub struct DirEntry {
+    pub name: String,
+    pub is_directory: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+// Entries of a directory on disk, symlinks are followed
+async fn read_disk_dir(directory: &Path, entries: &mut Vec<DirEntry>) -> bool {
+    let mut read_dir = match fs::read_dir(directory).await {
+        Ok(read_dir) => read_dir,
+        Err(_) => return false,
+    };
+    while let Ok(Some(entry)) = read_dir.next_entry().await {
+        let metadata: Metadata = match fs::metadata(entry.path()).await {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        entries.push(DirEntry {
+            name: entry.file_name().to_string_lossy().to_string(),
+            is_directory: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata.modified().ok(),
+        });
+    }
+    return true;
+}
+
+#[cfg(feature = "embed")]
+fn read_embedded_dir(relative: &Path, entries: &mut Vec<DirEntry>) -> bool {
+    let directory: &Dir<'static> = if relative.as_os_str().is_empty() {
+        &PUBLIC
+    } else {
+        match PUBLIC.get_dir(relative) {
+            Some(directory) => directory,
+            None => return false,
+        }
+    };
+    for entry in directory.entries() {
+        let name: String = match entry.path().file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => continue,
+        };
+        entries.push(match entry {
+            include_dir::DirEntry::Dir(_) => DirEntry {
+                name,
+                is_directory: true,
+                size: 0,
+                modified: None,
+            },
+            include_dir::DirEntry::File(file) => DirEntry {
+                name,
+                is_directory: false,
+                size: file.contents().len() as u64,
+                modified: file
+                    .metadata()
+                    .map(|metadata: &include_dir::Metadata| metadata.modified()),
+            },
+        });
+    }
+    return true;
+}
+
+#[cfg(not(feature = "embed"))]
+fn read_embedded_dir(_relative: &Path, _entries: &mut Vec<DirEntry>) -> bool {
+    return false;
+}
+
+// Entries of a directory requested relative to the web root, looked for in the same places as the files.
+// None when it isn't a directory anywhere, an entry found in more places is listed once.
+pub async fn read_dir(path: &str) -> Option<Vec<DirEntry>> {
+    let relative: PathBuf = normalize(path).ok()?;
+    let mut entries: Vec<DirEntry> = Vec::new();
+    let mut found: bool = false;
+    if relative == Path::new("images") {
+        if let Some(uploads) = resolve_in(&config::upload_dir(), Path::new("")).await {
+            let mut uploaded: Vec<DirEntry> = Vec::new();
+            found |= read_disk_dir(&uploads, &mut uploaded).await;
+            // Only the images can be opened from the upload directory
+            entries.extend(
+                uploaded
+                    .into_iter()
+                    .filter(|entry: &DirEntry| !entry.is_directory && is_raster_image(&entry.name)),
+            );
+        }
+    }
+    if disk_assets() {
+        if let Some(directory) = resolve_in(&config::web_root(), &relative).await {
+            found |= read_disk_dir(&directory, &mut entries).await;
+        }
+    }
+    found |= read_embedded_dir(&relative, &mut entries);
+    if !found {
+        return None;
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    entries.retain(|entry: &DirEntry| {
+        if names.contains(&entry.name) {
+            return false;
+        }
+        names.push(entry.name.clone());
+        return true;
+    });
+    return Some(entries);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(path: &str) -> Option<String> {
+        return normalize(path)
+            .ok()
+            .map(|path: PathBuf| path.to_string_lossy().to_string());
+    }
+
+    fn rejected(path: &str) -> bool {
+        return matches!(normalize(path), Err(ResolveError::BadRequest));
+    }
+
+    #[test]
+    fn dot_and_empty_segments_are_removed() {
+        assert_eq!(
+            normalized("/pages/index.html").as_deref(),
+            Some("pages/index.html")
+        );
+        assert_eq!(
+            normalized("//pages//./index.html").as_deref(),
+            Some("pages/index.html")
+        );
+        assert_eq!(normalized("/./pages/.").as_deref(), Some("pages"));
+        assert_eq!(normalized("/").as_deref(), Some(""));
+        assert_eq!(normalized("").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn parent_segments_stay_inside_the_root() {
+        assert_eq!(
+            normalized("/pages/../index.html").as_deref(),
+            Some("index.html")
+        );
+        assert_eq!(normalized("/a/b/../../c").as_deref(), Some("c"));
+        assert!(rejected("/.."));
+        assert!(rejected("/../etc/passwd"));
+        assert!(rejected("/pages/../../etc/passwd"));
+        assert!(rejected("/./../pages"));
+    }
+
+    #[test]
+    fn encoded_segments_are_decoded_first() {
+        assert_eq!(
+            normalized("/pages%2findex.html").as_deref(),
+            Some("pages/index.html")
+        );
+        assert_eq!(
+            normalized("/pages/%2e%2e/index.html").as_deref(),
+            Some("index.html")
+        );
+        assert_eq!(normalized("/%2E/pages").as_deref(), Some("pages"));
+        assert!(rejected("/%2e%2e/etc/passwd"));
+        assert!(rejected("/%2e%2e%2fetc%2fpasswd"));
+        assert!(rejected("/pages%2f..%2f..%2fetc"));
+    }
+
+    #[test]
+    fn null_bytes_and_invalid_encodings_are_rejected() {
+        assert!(rejected("/index.html%00.png"));
+        assert!(rejected("/%00"));
+        assert!(rejected("/%ff%fe"));
+    }
+}
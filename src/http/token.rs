@@ -2,6 +2,7 @@ use json::JsonValue;
 use rusqlite::Connection;
 
 use crate::db::dbconn;
+use crate::profiles::{self, Profile};
 
 pub async fn get_userdata(token: &str) -> JsonValue {
     let dbconn: Connection = dbconn();
@@ -18,14 +19,15 @@ pub async fn get_userdata(token: &str) -> JsonValue {
     let user: JsonValue = if dbuser.is_empty() {
         json::parse("{}").unwrap()
     } else {
-        json::parse(&format!(
-            r#"{{
-                "email": "{}",
-                "token": "{}"
-            }}"#,
-            dbuser, token
-        ))
-        .unwrap()
+        let profile: Profile = profiles::ensure(&dbconn, &dbuser);
+        json::object! {
+            email: dbuser,
+            token: token,
+            user_id: profile.user_id,
+            handle: profile.handle.clone(),
+            display_name: profile.name(),
+            avatar: profile.avatar_url()
+        }
     };
 
     return user;
@@ -0,0 +1,106 @@
+use json::JsonValue;
+use rusqlite::Connection;
+
+use crate::config;
+use crate::db::dbconn;
+use crate::follows;
+use crate::http::handle_get::check_template;
+use crate::http::permalink::DESCRIPTION_LENGTH;
+use crate::http::static_files;
+use crate::markdown::{escape_html, plain_text};
+use crate::profiles::{self, Profile};
+
+fn render_posts(dbconn: &Connection, email: &str) -> String {
+    let mut stmt = dbconn
+        .prepare(
+            "SELECT post_id, title, publish_at FROM posts
+                WHERE email = ?1 AND status = 'published' AND publish_at <= CURRENT_TIMESTAMP
+                ORDER BY publish_at DESC",
+        )
+        .unwrap();
+    let posts_iter = stmt.query_map([email], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    });
+
+    let mut posts: String = String::new();
+    for post in posts_iter.unwrap() {
+        let post = post.unwrap();
+        posts.push_str(&format!(
+            r#"<a class="flex justify-between items-center link link-hover" href="/post/{}"><span class="font-bold">{}</span><span class="text-xs text-gray-500">{}</span></a>"#,
+            post.0,
+            escape_html(&post.1),
+            escape_html(&format!("{} UTC", post.2))
+        ));
+    }
+    if posts.is_empty() {
+        return r#"<div class="text-center text-gray-500">No posts yet</div>"#.to_string();
+    }
+    return posts;
+}
+
+// Follow/unfollow button for logged in users, except on their own profile
+fn render_follow_button(dbconn: &Connection, profile: &Profile, viewer: Option<&str>) -> String {
+    let viewer: &str = match viewer {
+        Some(viewer) if viewer != profile.email => viewer,
+        _ => return String::new(),
+    };
+    let following: bool = follows::is_following(dbconn, viewer, &profile.email);
+    return format!(
+        r#"<button id="follow" class="btn btn-sm {}" data-handle="{}" data-following="{}" onclick="toggleFollow(this)">{}</button>"#,
+        if following {
+            "btn-outline"
+        } else {
+            "btn-primary"
+        },
+        escape_html(&profile.handle),
+        following,
+        if following { "Unfollow" } else { "Follow" }
+    );
+}
+
+pub async fn get_user_page(
+    handle: &str,
+    headers: &[(String, String)],
+    viewer: Option<&str>,
+) -> Option<String> {
+    let handle: String = urlencoding::decode(handle).ok()?.to_string();
+    let dbconn: Connection = dbconn();
+    let profile: Profile = profiles::by_handle(&dbconn, &handle)?;
+
+    let canonical: String = format!("{}/u/{}", config::public_url(headers), profile.handle);
+    let website_html: String = if profile.website.is_empty() {
+        String::new()
+    } else {
+        format!(
+            r#"<a class="link link-primary" href="{}" rel="nofollow noopener" target="_blank">{}</a>"#,
+            escape_html(&profile.website),
+            escape_html(&profile.website)
+        )
+    };
+
+    let counts: JsonValue = follows::counts(&dbconn, &profile.email);
+    let values: JsonValue = json::object! {
+        name: escape_html(&profile.name()),
+        handle: escape_html(&profile.handle),
+        description: escape_html(&plain_text(&profile.bio, DESCRIPTION_LENGTH)),
+        canonical: escape_html(&canonical),
+        avatar: escape_html(&profile.avatar_url()),
+        website_html: website_html,
+        followers: counts["followers"].clone(),
+        following: counts["following"].clone(),
+        follow_button: render_follow_button(&dbconn, &profile, viewer),
+        bio: escape_html(&profile.bio),
+        posts_html: render_posts(&dbconn, &profile.email)
+    };
+
+    let mut contents: String = static_files::read_to_string("pages/user.html").await.ok()?;
+    let contents: String = check_template(&mut contents, values).await;
+    return Some(format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n{}",
+        contents
+    ));
+}
@@ -0,0 +1,320 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use colored::Colorize;
+use sha1::{Digest, Sha1};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::{self, Event};
+use crate::http::request::find_header;
+use crate::http::token::auth_token;
+
+const PATH: &str = "/api/ws";
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+const CLOSE_GOING_AWAY: u16 = 1001;
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INVALID_DATA: u16 = 1007;
+const CLOSE_POLICY_VIOLATION: u16 = 1008;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+enum Close {
+    // The client started the closing handshake, the payload is its status code
+    Reply(Vec<u8>),
+    // The client broke the protocol, the connection is closed with this status code
+    Error(u16),
+}
+
+// Frames received from the client that haven't been handled yet
+struct Messages {
+    buffer: Vec<u8>,
+    fragments: Vec<u8>,
+    opcode: Option<u8>,
+}
+
+pub fn is_upgrade(method: &str, headers: &[(String, String)]) -> bool {
+    let upgrade: bool = find_header(headers, "Upgrade")
+        .is_some_and(|upgrade: &str| upgrade.trim().eq_ignore_ascii_case("websocket"));
+    let connection: bool = find_header(headers, "Connection").is_some_and(|connection: &str| {
+        connection
+            .split(',')
+            .any(|token: &str| token.trim().eq_ignore_ascii_case("upgrade"))
+    });
+    return method == "GET" && upgrade && connection;
+}
+
+fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(GUID.as_bytes());
+    return STANDARD.encode(hasher.finalize());
+}
+
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame: Vec<u8> = Vec::with_capacity(payload.len() + 10);
+    // Server frames are never fragmented nor masked
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        length if length < 126 => frame.push(length as u8),
+        length if length <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    return frame;
+}
+
+// Parses the frame at the start of the buffer, Ok(None) when more bytes are needed
+fn parse_frame(buffer: &[u8]) -> Result<Option<(Frame, usize)>, u16> {
+    if buffer.len() < 2 {
+        return Ok(None);
+    }
+    let fin: bool = buffer[0] & 0x80 != 0;
+    let opcode: u8 = buffer[0] & 0x0F;
+    // No extension is negotiated, so the reserved bits must be 0
+    if buffer[0] & 0x70 != 0 {
+        return Err(CLOSE_PROTOCOL_ERROR);
+    }
+    // Every frame sent by a client must be masked (RFC 6455 5.1)
+    if buffer[1] & 0x80 == 0 {
+        return Err(CLOSE_PROTOCOL_ERROR);
+    }
+
+    let (length, offset): (u64, usize) = match buffer[1] & 0x7F {
+        126 => match buffer.get(2..4) {
+            Some(length) => (u16::from_be_bytes([length[0], length[1]]) as u64, 4),
+            None => return Ok(None),
+        },
+        127 => match buffer.get(2..10) {
+            Some(length) => (u64::from_be_bytes(length.try_into().unwrap()), 10),
+            None => return Ok(None),
+        },
+        length => (length as u64, 2),
+    };
+    // Control frames can't be fragmented and carry at most 125 bytes
+    if opcode >= OPCODE_CLOSE && (!fin || length > 125) {
+        return Err(CLOSE_PROTOCOL_ERROR);
+    }
+    if length > MAX_MESSAGE_SIZE as u64 {
+        return Err(CLOSE_TOO_BIG);
+    }
+
+    let length: usize = length as usize;
+    let (mask, payload): (&[u8], &[u8]) = match buffer.get(offset..offset + 4 + length) {
+        Some(frame) => frame.split_at(4),
+        None => return Ok(None),
+    };
+    let payload: Vec<u8> = payload
+        .iter()
+        .enumerate()
+        .map(|(index, byte): (usize, &u8)| byte ^ mask[index % 4])
+        .collect();
+    return Ok(Some((
+        Frame {
+            fin,
+            opcode,
+            payload,
+        },
+        offset + 4 + length,
+    )));
+}
+
+impl Messages {
+    // Handles every complete frame in the buffer and returns the frames to send back
+    fn process(&mut self) -> Result<Vec<Vec<u8>>, Close> {
+        let mut replies: Vec<Vec<u8>> = Vec::new();
+        while let Some((frame, used)) = parse_frame(&self.buffer).map_err(Close::Error)? {
+            self.buffer.drain(..used);
+            match frame.opcode {
+                OPCODE_PING => replies.push(encode_frame(OPCODE_PONG, &frame.payload)),
+                OPCODE_PONG => {}
+                OPCODE_CLOSE => {
+                    return match frame.payload.len() {
+                        1 => Err(Close::Error(CLOSE_PROTOCOL_ERROR)),
+                        0 => Err(Close::Reply(Vec::new())),
+                        _ => Err(Close::Reply(frame.payload[..2].to_vec())),
+                    };
+                }
+                OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION => self.fragment(frame)?,
+                _ => return Err(Close::Error(CLOSE_PROTOCOL_ERROR)),
+            }
+        }
+        return Ok(replies);
+    }
+
+    // Reassembles fragmented messages, the messages themselves are not used by the server
+    fn fragment(&mut self, frame: Frame) -> Result<(), Close> {
+        match (frame.opcode, self.opcode) {
+            (OPCODE_CONTINUATION, None) => return Err(Close::Error(CLOSE_PROTOCOL_ERROR)),
+            (OPCODE_CONTINUATION, Some(_)) => {}
+            (_, Some(_)) => return Err(Close::Error(CLOSE_PROTOCOL_ERROR)),
+            (opcode, None) => self.opcode = Some(opcode),
+        }
+        if self.fragments.len() + frame.payload.len() > MAX_MESSAGE_SIZE {
+            return Err(Close::Error(CLOSE_TOO_BIG));
+        }
+        self.fragments.extend_from_slice(&frame.payload);
+        if !frame.fin {
+            return Ok(());
+        }
+
+        let opcode: Option<u8> = self.opcode.take();
+        let message: Vec<u8> = std::mem::take(&mut self.fragments);
+        if opcode == Some(OPCODE_TEXT) && std::str::from_utf8(&message).is_err() {
+            return Err(Close::Error(CLOSE_INVALID_DATA));
+        }
+        return Ok(());
+    }
+}
+
+// Sends a close frame and waits a little for the client to answer before dropping the connection
+async fn close(socket: &mut TcpStream, code: u16) {
+    if socket
+        .write_all(&encode_frame(OPCODE_CLOSE, &code.to_be_bytes()))
+        .await
+        .is_err()
+    {
+        return;
+    }
+    let mut chunk: [u8; 1024] = [0; 1024];
+    let _ = tokio::time::timeout(CLOSE_TIMEOUT, async {
+        while let Ok(read) = socket.read(&mut chunk).await {
+            if read == 0 {
+                break;
+            }
+        }
+    })
+    .await;
+}
+
+fn handshake_error(status: &str, headers: &str) -> String {
+    return format!(
+        "HTTP/1.1 {}\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+        status, headers
+    );
+}
+
+pub async fn handle(mut socket: TcpStream, path: &str, headers: &[(String, String)]) {
+    let sha256_token: &str = match find_header(headers, "Cookie") {
+        Some(cookie) => cookie.split("token=").last().unwrap(),
+        None => "",
+    };
+    let key: &str = find_header(headers, "Sec-WebSocket-Key")
+        .unwrap_or("")
+        .trim();
+
+    let response: String = if path.split('?').next() != Some(PATH) {
+        handshake_error("404 NOT FOUND", "")
+    } else if find_header(headers, "Sec-WebSocket-Version").map(str::trim) != Some("13") {
+        handshake_error("426 UPGRADE REQUIRED", "Sec-WebSocket-Version: 13\r\n")
+    } else if STANDARD.decode(key).map(|key: Vec<u8>| key.len()) != Ok(16) {
+        handshake_error("400 BAD REQUEST", "")
+    } else if !auth_token(sha256_token).await {
+        handshake_error("401 UNAUTHORIZED", "")
+    } else {
+        format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(key)
+        )
+    };
+    if socket.write_all(response.as_bytes()).await.is_err() || !response.contains(" 101 ") {
+        return;
+    }
+    println!("WebSocket client {}", "connected".green());
+
+    let mut receiver = events::subscribe();
+    let mut messages: Messages = Messages {
+        buffer: Vec::new(),
+        fragments: Vec::new(),
+        opcode: None,
+    };
+    let mut chunk: [u8; 4096] = [0; 4096];
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    ping.tick().await;
+    let mut awaiting_pong: bool = false;
+
+    loop {
+        tokio::select! {
+            read = socket.read(&mut chunk) => {
+                let read: usize = match read {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => read,
+                };
+                // Any data from the client proves the connection is still alive
+                awaiting_pong = false;
+                messages.buffer.extend_from_slice(&chunk[..read]);
+                match messages.process() {
+                    Ok(replies) => {
+                        for reply in replies {
+                            if socket.write_all(&reply).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Err(Close::Reply(payload)) => {
+                        let _ = socket.write_all(&encode_frame(OPCODE_CLOSE, &payload)).await;
+                        break;
+                    }
+                    Err(Close::Error(code)) => {
+                        close(&mut socket, code).await;
+                        break;
+                    }
+                }
+            }
+            event = receiver.recv() => {
+                let event: Event = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        println!("WebSocket client lagged behind, {} events skipped", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => {
+                        close(&mut socket, CLOSE_GOING_AWAY).await;
+                        break;
+                    }
+                };
+                let frame: Vec<u8> = encode_frame(OPCODE_TEXT, event.to_json().as_bytes());
+                if socket.write_all(&frame).await.is_err() {
+                    break;
+                }
+            }
+            _ = ping.tick() => {
+                // The session may have ended (logout) since the upgrade
+                if !auth_token(sha256_token).await {
+                    close(&mut socket, CLOSE_POLICY_VIOLATION).await;
+                    break;
+                }
+                if awaiting_pong {
+                    break;
+                }
+                awaiting_pong = true;
+                if socket.write_all(&encode_frame(OPCODE_PING, b"")).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    println!("WebSocket client {}", "disconnected".red());
+}
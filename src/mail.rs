@@ -0,0 +1,164 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config;
+use crate::http::date;
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// Delivers plain text emails, the implementation is picked with the MAIL_TRANSPORT setting
+pub trait MailTransport: Send + Sync {
+    fn send(&self, mail: &Mail) -> std::io::Result<()>;
+}
+
+// Prints the emails instead of sending them, used when no transport is configured
+pub struct LogTransport;
+
+// Pipes the emails to a sendmail compatible command (sendmail, msmtp, ...)
+pub struct SendmailTransport {
+    pub command: String,
+}
+
+// Plain SMTP without TLS nor authentication, meant for a local relay
+pub struct SmtpTransport {
+    pub address: String,
+}
+
+// Header values must not contain line breaks, they would allow injecting headers
+fn header_value(value: &str) -> String {
+    return value.replace(['\r', '\n'], " ");
+}
+
+fn message(from: &str, mail: &Mail) -> String {
+    let now: i64 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration: Duration| duration.as_secs() as i64)
+        .unwrap_or(0);
+    let body: String = mail.body.replace("\r\n", "\n").replace('\n', "\r\n");
+    return format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n{}\r\n",
+        header_value(from),
+        header_value(&mail.to),
+        header_value(&mail.subject),
+        date::to_rfc822(now),
+        body
+    );
+}
+
+impl MailTransport for LogTransport {
+    fn send(&self, mail: &Mail) -> std::io::Result<()> {
+        println!("Mail to {}: {}\n{}", mail.to, mail.subject, mail.body);
+        return Ok(());
+    }
+}
+
+impl MailTransport for SendmailTransport {
+    fn send(&self, mail: &Mail) -> std::io::Result<()> {
+        let mut child = Command::new(&self.command)
+            .args(["-t", "-i"])
+            .stdin(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(message(&config::mail_from(), mail).as_bytes())?;
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(std::io::Error::other(format!(
+                "{} exited with {}",
+                self.command, status
+            )));
+        }
+        return Ok(());
+    }
+}
+
+// Reads a (possibly multiline) SMTP reply and checks its code
+fn smtp_reply(reader: &mut BufReader<TcpStream>, expected: &[&str]) -> std::io::Result<()> {
+    loop {
+        let mut line: String = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "SMTP server closed the connection",
+            ));
+        }
+        // "250-..." lines are followed by more lines, "250 ..." is the last one
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        if expected.iter().any(|code: &&str| line.starts_with(code)) {
+            return Ok(());
+        }
+        return Err(std::io::Error::other(format!(
+            "unexpected SMTP reply: {}",
+            line.trim_end()
+        )));
+    }
+}
+
+impl MailTransport for SmtpTransport {
+    fn send(&self, mail: &Mail) -> std::io::Result<()> {
+        let from: String = config::mail_from();
+        let stream: TcpStream = TcpStream::connect(&self.address)?;
+        stream.set_read_timeout(Some(SMTP_TIMEOUT))?;
+        stream.set_write_timeout(Some(SMTP_TIMEOUT))?;
+        let mut writer: TcpStream = stream.try_clone()?;
+        let mut reader: BufReader<TcpStream> = BufReader::new(stream);
+
+        smtp_reply(&mut reader, &["220"])?;
+        writer.write_all(b"EHLO localhost\r\n")?;
+        smtp_reply(&mut reader, &["250"])?;
+        writer.write_all(format!("MAIL FROM:<{}>\r\n", header_value(&from)).as_bytes())?;
+        smtp_reply(&mut reader, &["250"])?;
+        writer.write_all(format!("RCPT TO:<{}>\r\n", header_value(&mail.to)).as_bytes())?;
+        smtp_reply(&mut reader, &["250", "251"])?;
+        writer.write_all(b"DATA\r\n")?;
+        smtp_reply(&mut reader, &["354"])?;
+
+        // Lines starting with a dot are escaped so they don't end the DATA section
+        let data: String = message(&from, mail)
+            .split("\r\n")
+            .map(|line: &str| {
+                if line.starts_with('.') {
+                    format!(".{}", line)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\r\n");
+        writer.write_all(data.as_bytes())?;
+        writer.write_all(b".\r\n")?;
+        smtp_reply(&mut reader, &["250"])?;
+        writer.write_all(b"QUIT\r\n")?;
+        return Ok(());
+    }
+}
+
+pub fn transport() -> Box<dyn MailTransport> {
+    return match config::mail_transport() {
+        Some(transport) if transport == "sendmail" => Box::new(SendmailTransport {
+            command: "sendmail".to_string(),
+        }),
+        Some(transport) if transport.starts_with("sendmail:") => Box::new(SendmailTransport {
+            command: transport.trim_start_matches("sendmail:").to_string(),
+        }),
+        Some(transport) if transport.starts_with("smtp://") => Box::new(SmtpTransport {
+            address: transport
+                .trim_start_matches("smtp://")
+                .trim_end_matches('/')
+                .to_string(),
+        }),
+        _ => Box::new(LogTransport),
+    };
+}
@@ -1,9 +1,24 @@
+mod bookmarks;
+mod comments;
+mod config;
 mod db;
+mod events;
+mod follows;
 mod http;
+mod mail;
+mod markdown;
+mod mentions;
 mod multipart;
+mod notifications;
+mod pagination;
+mod profiles;
+mod publishing;
+mod reactions;
+mod tags;
 
 use colored::Colorize;
-use http::{handle_get, handle_post};
+use http::body::Body;
+use http::{handle_delete, handle_get, handle_patch, handle_post, sse, websocket};
 use std::str;
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
@@ -131,31 +146,68 @@ async fn handle_connection(mut socket: tokio::net::TcpStream) {
         .1
         .to_string();
 
+    if websocket::is_upgrade(&method, &headers) {
+        websocket::handle(socket, &path, &headers).await;
+        return;
+    }
+
+    if sse::is_stream(&method, &path) {
+        sse::handle(socket, &path, &headers).await;
+        return;
+    }
+
     if content_type.contains("multipart/form-data") && path == "/api/upload" && method == "POST" {
         let response: String = multipart::upload(headers, &body, complete_buffer).await;
         socket.try_write(response.as_bytes()).unwrap();
         return;
     }
 
-    let response: (String, Vec<u8>) = match method.as_str() {
+    if content_type.contains("multipart/form-data")
+        && path == "/api/users/me/avatar"
+        && method == "POST"
+    {
+        let response: String = multipart::upload_avatar(headers, &body, complete_buffer).await;
+        socket.write_all(response.as_bytes()).await.unwrap();
+        return;
+    }
+
+    let response: (String, Body) = match method.as_str() {
         "GET" => handle_get::get(path, headers).await,
-        "POST" => handle_post::post(path, headers, body).await,
+        "POST" => {
+            let (contents, body) = handle_post::post(path, headers, body).await;
+            (contents, Body::from(body))
+        }
+        "PATCH" => {
+            let (contents, body) = handle_patch::patch(path, headers, body).await;
+            (contents, Body::from(body))
+        }
+        "DELETE" => {
+            let (contents, body) = handle_delete::delete(path, headers).await;
+            (contents, Body::from(body))
+        }
         _ => (
             "HTTP/1.1 405 METHOD NOT ALLOWED\r\n\r\n".to_string(),
-            Vec::new(),
+            Body::empty(),
         ),
     };
 
     socket.write_all(response.0.as_bytes()).await.unwrap();
 
-    if response.1.len() > 0 {
-        socket.write_all(&response.1).await.unwrap();
+    // The client can close the connection before a large file is fully sent
+    if let Err(error) = response.1.write_to(&mut socket).await {
+        println!("Error writing the response: {}", error.to_string().red());
     }
 }
 
 #[tokio::main]
 async fn main() {
     db::init_db();
+    // The images are still uploaded on disk when the public folder is embedded
+    if let Err(error) = std::fs::create_dir_all(config::upload_dir()) {
+        println!("Error creating the upload directory: {}", error);
+    }
+    tokio::spawn(publishing::scheduler());
+    tokio::spawn(notifications::digest_scheduler());
 
     let ports: Vec<u16> = vec![80, 8000, 8080, 8888];
     let mut port_index: usize = 0;
@@ -171,7 +223,7 @@ async fn main() {
             }
             Err(_) => {
                 println!("Port {} is in use, trying next port...", ports[port_index]);
-                if ports[port_index] == *ports.last().unwrap(){
+                if ports[port_index] == *ports.last().unwrap() {
                     println!("All safe ports are in use, exiting...");
                     break;
                 }
@@ -0,0 +1,274 @@
+use pulldown_cmark::{html, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd};
+
+use crate::profiles::{HANDLE_MAX_LENGTH, HANDLE_MIN_LENGTH};
+
+const SAFE_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+fn safe_url(url: CowStr) -> CowStr {
+    let cleaned: String = url
+        .chars()
+        .filter(|c: &char| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_lowercase();
+
+    // Relative URLs (no scheme before the first path/query/fragment delimiter) are fine
+    let scheme_end: Option<usize> = cleaned.find(':');
+    let delimiter: Option<usize> = cleaned.find(['/', '?', '#']);
+    let scheme: &str = match (scheme_end, delimiter) {
+        (Some(colon), Some(delim)) if delim < colon => return url,
+        (Some(colon), _) => &cleaned[..colon],
+        (None, _) => return url,
+    };
+
+    if SAFE_SCHEMES.contains(&scheme) {
+        return url;
+    }
+    return CowStr::Borrowed("#");
+}
+
+fn sanitize(event: Event) -> Event {
+    match event {
+        // Raw HTML is never passed through, it is shown as text instead
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Link {
+            link_type,
+            dest_url: safe_url(dest_url),
+            title,
+            id,
+        }),
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Image {
+            link_type,
+            dest_url: safe_url(dest_url),
+            title,
+            id,
+        }),
+        event => event,
+    }
+}
+
+fn is_handle_char(c: char) -> bool {
+    return c.is_ascii_alphanumeric() || c == '_' || c == '-';
+}
+
+// Byte ranges of the @handle mentions in a text, "@" included. Emails are not mentions.
+fn mention_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut previous: Option<char> = None;
+    for (index, c) in text.char_indices() {
+        if c == '@'
+            && !previous.is_some_and(|previous: char| is_handle_char(previous) || previous == '.')
+        {
+            let length: usize = text[index + 1..]
+                .chars()
+                .take_while(|c: &char| is_handle_char(*c))
+                .count();
+            if (HANDLE_MIN_LENGTH..=HANDLE_MAX_LENGTH).contains(&length) {
+                spans.push((index, index + 1 + length));
+            }
+        }
+        previous = Some(c);
+    }
+    return spans;
+}
+
+// Text events of the source that can contain mentions, i.e. outside of code and links
+fn mention_texts<'a>(
+    parser: impl Iterator<Item = Event<'a>>,
+) -> impl Iterator<Item = (Event<'a>, bool)> {
+    let mut in_code: bool = false;
+    let mut links: usize = 0;
+    return parser.map(move |event: Event<'a>| {
+        match &event {
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(TagEnd::CodeBlock) => in_code = false,
+            Event::Start(Tag::Link { .. }) => links += 1,
+            Event::End(TagEnd::Link) => links = links.saturating_sub(1),
+            _ => {}
+        }
+        let mentionable: bool = matches!(event, Event::Text(_)) && !in_code && links == 0;
+        (event, mentionable)
+    });
+}
+
+// Lowercase handles mentioned in the source, without duplicates
+pub fn mentions(source: &str) -> Vec<String> {
+    let mut handles: Vec<String> = Vec::new();
+    for (event, mentionable) in mention_texts(Parser::new_ext(source, options()).map(sanitize)) {
+        let text: CowStr = match event {
+            Event::Text(text) if mentionable => text,
+            _ => continue,
+        };
+        for (start, end) in mention_spans(&text) {
+            let handle: String = text[start + 1..end].to_lowercase();
+            if !handles.contains(&handle) {
+                handles.push(handle);
+            }
+        }
+    }
+    return handles;
+}
+
+// Splits a text around the mentions of the given handles, which become links to their profiles
+fn link_mentions<'a>(text: CowStr<'a>, handles: &[String]) -> Vec<Event<'a>> {
+    let mut events: Vec<Event> = Vec::new();
+    let mut last: usize = 0;
+    for (start, end) in mention_spans(&text) {
+        let handle: String = text[start + 1..end].to_lowercase();
+        if !handles.contains(&handle) {
+            continue;
+        }
+        if start > last {
+            events.push(Event::Text(CowStr::from(text[last..start].to_string())));
+        }
+        events.push(Event::Start(Tag::Link {
+            link_type: LinkType::Inline,
+            dest_url: CowStr::from(format!("/u/{}", handle)),
+            title: CowStr::from(""),
+            id: CowStr::from(""),
+        }));
+        events.push(Event::Text(CowStr::from(text[start..end].to_string())));
+        events.push(Event::End(TagEnd::Link));
+        last = end;
+    }
+    if last == 0 {
+        return vec![Event::Text(text)];
+    }
+    if last < text.len() {
+        events.push(Event::Text(CowStr::from(text[last..].to_string())));
+    }
+    return events;
+}
+
+fn options() -> Options {
+    let mut options: Options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    return options;
+}
+
+pub fn render(source: &str) -> String {
+    return render_with_mentions(source, &[]);
+}
+
+// Same as render, with the mentions of the given handles linked to their profiles
+pub fn render_with_mentions(source: &str, handles: &[String]) -> String {
+    let parser = Parser::new_ext(source, options()).map(sanitize);
+    let events =
+        mention_texts(parser).flat_map(|(event, mentionable): (Event, bool)| match event {
+            Event::Text(text) if mentionable && !handles.is_empty() => link_mentions(text, handles),
+            event => vec![event],
+        });
+    let mut rendered: String = String::new();
+    html::push_html(&mut rendered, events);
+    return rendered;
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            // Control characters are not allowed in XML 1.0, the feeds are escaped with this too
+            c if c.is_control() && !['\n', '\r', '\t'].contains(&c) => {}
+            c => escaped.push(c),
+        }
+    }
+    return escaped;
+}
+
+// Plain text of a Markdown source, used for previews and meta descriptions
+pub fn plain_text(source: &str, max_length: usize) -> String {
+    let mut text: String = String::new();
+    for event in Parser::new_ext(source, options()) {
+        match event {
+            Event::Text(part) | Event::Code(part) => text.push_str(&part),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let text: String = words.join(" ");
+    if text.chars().count() <= max_length {
+        return text;
+    }
+    return text
+        .chars()
+        .take(max_length - 1)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+        + "…";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_html_is_escaped() {
+        let rendered: String =
+            render("<script>alert(1)</script>\n\nHi <img src=x onerror=alert(1)>");
+        assert!(!rendered.contains("<script>"));
+        assert!(!rendered.contains("<img"));
+        assert!(rendered.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(rendered.contains("&lt;img src=x onerror=alert(1)&gt;"));
+    }
+
+    #[test]
+    fn unsafe_urls_become_anchors() {
+        for url in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            "java\tscript:alert(1)",
+            "data:text/html;base64,PHNjcmlwdD4=",
+            "vbscript:msgbox(1)",
+        ] {
+            let link: String = render(&format!("[x](<{}>)", url));
+            assert!(link.contains("href=\"#\""), "{}", link);
+            let image: String = render(&format!("![x](<{}>)", url));
+            assert!(image.contains("src=\"#\""), "{}", image);
+        }
+    }
+
+    #[test]
+    fn safe_urls_are_kept() {
+        assert!(render("[x](https://example.com/?a=1&b=2)")
+            .contains("href=\"https://example.com/?a=1&amp;b=2\""));
+        assert!(render("[x](mailto:a@b.c)").contains("href=\"mailto:a@b.c\""));
+        assert!(render("[x](/post/1#comments)").contains("href=\"/post/1#comments\""));
+        assert!(render("[x](./a:b)").contains("href=\"./a:b\""));
+    }
+
+    #[test]
+    fn attributes_are_quoted() {
+        assert_eq!(
+            render("[x](https://a.b/\"onmouseover=\"alert(1) \"t\\\" onclick=\\\"y\")"),
+            "<p><a href=\"https://a.b/%22onmouseover=%22alert(1)\" title=\"t&quot; onclick=&quot;y\">x</a></p>\n"
+        );
+        assert!(render("![a\" onerror=\"x](/a.png)").contains("alt=\"a&quot; onerror=&quot;x\""));
+    }
+
+    #[test]
+    fn plain_text_strips_markup() {
+        assert_eq!(
+            plain_text("# Title\n\n*some* `code` <b>", 100),
+            "Title some code"
+        );
+        assert_eq!(plain_text("one two three", 8), "one two…");
+    }
+}
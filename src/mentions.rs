@@ -0,0 +1,54 @@
+use rusqlite::Connection;
+
+use crate::markdown;
+use crate::profiles::{self, Profile};
+
+// Users mentioned in a Markdown source, mentions of unknown handles are ignored
+pub fn resolve(dbconn: &Connection, source: &str) -> Vec<Profile> {
+    return markdown::mentions(source)
+        .iter()
+        .filter_map(|handle: &String| profiles::by_handle(dbconn, handle))
+        .collect();
+}
+
+// Renders a post or comment with the mentions linked to the profiles of the users
+pub fn render(dbconn: &Connection, source: &str) -> String {
+    let handles: Vec<String> = resolve(dbconn, source)
+        .into_iter()
+        .map(|profile: Profile| profile.handle)
+        .collect();
+    return markdown::render_with_mentions(source, &handles);
+}
+
+// Stores who is mentioned in a post (comment_id is None) or in a comment
+pub fn save(dbconn: &Connection, source: &str, post_id: i64, comment_id: Option<i64>) {
+    for profile in resolve(dbconn, source) {
+        dbconn
+            .execute(
+                "INSERT INTO mentions (email, post_id, comment_id) VALUES (?1, ?2, ?3)",
+                [
+                    &profile.email as &dyn rusqlite::ToSql,
+                    &post_id as &dyn rusqlite::ToSql,
+                    &comment_id as &dyn rusqlite::ToSql,
+                ],
+            )
+            .unwrap();
+    }
+}
+
+// Emails of the users mentioned in a post (comment_id is None) or in a comment
+pub fn mentioned(dbconn: &Connection, post_id: i64, comment_id: Option<i64>) -> Vec<String> {
+    let mut stmt = dbconn
+        .prepare(
+            "SELECT email FROM mentions WHERE post_id = ?1 AND comment_id IS ?2 ORDER BY mention_id",
+        )
+        .unwrap();
+    let emails_iter = stmt.query_map(
+        [
+            &post_id as &dyn rusqlite::ToSql,
+            &comment_id as &dyn rusqlite::ToSql,
+        ],
+        |row| row.get::<_, String>(0),
+    );
+    return emails_iter.unwrap().map(|email| email.unwrap()).collect();
+}
@@ -1,11 +1,22 @@
+use crate::config;
 use crate::db::dbconn;
+use crate::http::static_files;
 use crate::http::token::get_userdata;
+use crate::mentions;
+use crate::profiles::{self, Profile};
+use crate::publishing;
+use crate::tags;
 use json::JsonValue;
+use rusqlite::Connection;
 use tokio;
 use uuid::Uuid;
 
 pub mod binary;
 
+// Uploads and avatars are served without login, so only raster images that can't carry scripts are accepted
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "gif", "webp"];
+const AVATAR_MAX_SIZE: usize = 2 * 1024 * 1024;
+
 fn image_name_db(image_name: String) -> String {
     if image_name.is_empty() {
         return image_name;
@@ -19,16 +30,17 @@ async fn save(
     image_data: Vec<u8>,
     image_name: String,
     email: &str,
+    post_tags: Vec<String>,
+    (status, publish_at): (String, Option<String>),
 ) -> String {
     println!(
-        "Title: {} Content: {} Image: {} Email: {}",
-        title, content, image_name, email
+        "Title: {} Content: {} Image: {} Email: {} Tags: {:?} Status: {} Publish at: {:?}",
+        title, content, image_name, email, post_tags, status, publish_at
     );
 
     if !image_name.is_empty() {
-        let mut file = tokio::fs::File::create(format!("public/images/{}", image_name))
-            .await
-            .unwrap();
+        let image_path: String = format!("{}/{}", config::upload_dir(), image_name);
+        let mut file = tokio::fs::File::create(image_path).await.unwrap();
 
         // write the image to the file
         tokio::io::AsyncWriteExt::write_all(&mut file, &image_data)
@@ -36,17 +48,29 @@ async fn save(
             .unwrap();
     }
 
-    dbconn()
+    let dbconn: Connection = dbconn();
+    dbconn
         .execute(
-            "INSERT INTO posts (title, content, image, email) VALUES (?1, ?2, ?3, ?4)",
-            &[
-                &title,
-                &content,
-                &image_name_db(image_name).as_str(),
-                &email,
+            "INSERT INTO posts (title, content, content_html, image, email, status, publish_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, CASE WHEN ?6 = 'draft' THEN NULL ELSE COALESCE(?7, CURRENT_TIMESTAMP) END)",
+            [
+                &title as &dyn rusqlite::ToSql,
+                &content as &dyn rusqlite::ToSql,
+                &mentions::render(&dbconn, content) as &dyn rusqlite::ToSql,
+                &image_name_db(image_name) as &dyn rusqlite::ToSql,
+                &email as &dyn rusqlite::ToSql,
+                &status as &dyn rusqlite::ToSql,
+                &publish_at as &dyn rusqlite::ToSql,
             ],
         )
         .unwrap();
+    let post_id: i64 = dbconn.last_insert_rowid();
+    tags::save(&dbconn, post_id, &post_tags);
+    mentions::save(&dbconn, content, post_id, None);
+
+    if status == publishing::PUBLISHED {
+        publishing::published(&dbconn, post_id);
+    }
 
     return "HTTP/1.1 301 MOVED PERMANENTLY\r\nLocation: /\r\n\r\n".to_string();
 }
@@ -96,6 +120,9 @@ pub async fn upload(
 
     let mut title: String = "".to_string();
     let mut content: String = "".to_string();
+    let mut post_tags: Vec<String> = Vec::new();
+    let mut status: String = String::new();
+    let mut publish_at: String = String::new();
     let mut image_name: String = String::new();
     let mut image_data: Vec<u8> = Vec::new();
 
@@ -147,15 +174,24 @@ pub async fn upload(
         let body_lines: Vec<&str> = lines
             [lines.iter().position(|line| line.is_empty()).unwrap() + 1..]
             .iter()
-            .map(|line| line.trim())
+            .map(|line| line.trim_end())
             .collect();
 
         match name {
             "title" => {
-                title = body_lines.join(" ");
+                title = body_lines.join(" ").trim().to_string();
             }
             "content" => {
-                content = urlencoding::encode(body_lines.join("\n").as_str()).to_string();
+                content = body_lines.join("\n");
+            }
+            "tags" => {
+                post_tags = tags::parse(body_lines.join(",").as_str());
+            }
+            "status" => {
+                status = body_lines.join("").trim().to_string();
+            }
+            "publish_at" => {
+                publish_at = body_lines.join("").trim().to_string();
             }
             "image" => {
                 image_name = content_disposition
@@ -167,17 +203,27 @@ pub async fn upload(
                 if image_name.is_empty() {
                     continue;
                 }
-                image_name = format!(
-                    "asset-{}.{}",
-                    Uuid::new_v4(),
-                    image_name.split('.').collect::<Vec<&str>>().last().unwrap()
-                );
+                let extension: String = match image_name.rsplit_once('.') {
+                    Some((_, extension)) => extension.trim().to_lowercase(),
+                    None => String::new(),
+                };
+                if !IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+                    return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string()
+                        + "The image must be a png, jpg, gif or webp image";
+                }
+                image_name = format!("asset-{}.{}", Uuid::new_v4(), extension);
                 image_data = binary::find_binary(complete_buffer.clone(), boundary.to_string());
             }
             _ => {}
         }
     }
 
+    let publication: (String, Option<String>) =
+        match publishing::resolve_status(&status, &publish_at) {
+            Ok(resolved) => resolved,
+            Err(error) => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + &error,
+        };
+
     let email: &str = decoded["email"].as_str().unwrap();
     let response: String = save(
         title.as_str(),
@@ -185,7 +231,83 @@ pub async fn upload(
         image_data,
         image_name,
         email,
+        post_tags,
+        publication,
     )
     .await;
     return response;
 }
+
+// Replaces the avatar of the user with the image sent in the "avatar" field
+pub async fn upload_avatar(
+    headers: Vec<(String, String)>,
+    body: &str,
+    complete_buffer: Vec<u8>,
+) -> String {
+    let sha256_token: &str = match headers
+        .iter()
+        .find(|header: &&(String, String)| header.0 == "Cookie")
+    {
+        Some(header) => header
+            .1
+            .split("token=")
+            .collect::<Vec<&str>>()
+            .last()
+            .unwrap(),
+        None => "",
+    };
+    let decoded: JsonValue = get_userdata(sha256_token).await;
+    if decoded["email"].is_null() {
+        return "HTTP/1.1 401 UNAUTHORIZED\r\n\r\n".to_string() + "401 UNAUTHORIZED";
+    }
+
+    let content_type: String = headers
+        .iter()
+        .find(|header: &&(String, String)| header.0 == "Content-Type")
+        .map(|header: &(String, String)| header.1.to_string())
+        .unwrap_or_default();
+    let boundary: &str = content_type
+        .split("boundary=")
+        .collect::<Vec<&str>>()
+        .last()
+        .unwrap();
+
+    let extension: String = match body
+        .split("filename=\"")
+        .nth(1)
+        .and_then(|filename: &str| filename.split('"').next())
+        .and_then(|filename: &str| filename.rsplit_once('.'))
+    {
+        Some((_, extension)) => extension.to_lowercase(),
+        None => return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "Missing avatar",
+    };
+    if !IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string()
+            + "The avatar must be a png, jpg, gif or webp image";
+    }
+    let image_data: Vec<u8> = binary::find_binary(complete_buffer, boundary.to_string());
+    if image_data.is_empty() {
+        return "HTTP/1.1 400 BAD REQUEST\r\n\r\n".to_string() + "Missing avatar";
+    }
+    if image_data.len() > AVATAR_MAX_SIZE {
+        return "HTTP/1.1 413 PAYLOAD TOO LARGE\r\n\r\n".to_string()
+            + "The avatar can be at most 2 MB";
+    }
+
+    let image_name: String = format!("avatar-{}.{}", Uuid::new_v4(), extension);
+    let image_path: String = format!("{}/{}", config::upload_dir(), image_name);
+    tokio::fs::write(image_path, &image_data).await.unwrap();
+
+    let dbconn: Connection = dbconn();
+    let profile: Profile = profiles::ensure(&dbconn, decoded["email"].as_str().unwrap());
+    profiles::set_avatar(&dbconn, profile.user_id, &image_name_db(image_name.clone()));
+    if let Some(path) = static_files::upload_path(&profile.avatar) {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+
+    let profile: Profile = profiles::ensure(&dbconn, &profile.email);
+    return format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{}",
+        profile.to_json().dump()
+    );
+}
@@ -0,0 +1,441 @@
+use colored::Colorize;
+use json::JsonValue;
+use rusqlite::Connection;
+use std::time::Duration;
+
+use crate::config;
+use crate::db::dbconn;
+use crate::mail::{self, Mail, MailTransport};
+use crate::markdown::plain_text;
+use crate::mentions;
+use crate::profiles;
+use crate::reactions::{self, ReactionKind, Target};
+
+pub const COMMENT: &str = "comment";
+pub const REPLY: &str = "reply";
+pub const REACTION: &str = "reaction";
+pub const MENTION: &str = "mention";
+
+const EXCERPT_LENGTH: usize = 100;
+const LIST_LENGTH: usize = 50;
+
+struct Notification {
+    notification_id: i64,
+    // The email of the user that commented or reacted, never sent to the client
+    actor: String,
+    actor_name: String,
+    kind: String,
+    post_id: i64,
+    post_title: String,
+    comment_id: Option<i64>,
+    detail: String,
+    read: bool,
+    datetime: String,
+}
+
+impl Notification {
+    fn message(&self) -> String {
+        let reaction: String = reactions::kinds()
+            .into_iter()
+            .find(|kind: &ReactionKind| kind.name == self.detail)
+            .map(|kind: ReactionKind| kind.emoji)
+            .unwrap_or(self.detail.clone());
+        return match self.kind.as_str() {
+            COMMENT => format!(
+                "{} commented on \"{}\": {}",
+                self.actor_name, self.post_title, self.detail
+            ),
+            REPLY => format!(
+                "{} replied to your comment on \"{}\": {}",
+                self.actor_name, self.post_title, self.detail
+            ),
+            MENTION if self.comment_id.is_some() => format!(
+                "{} mentioned you in a comment on \"{}\": {}",
+                self.actor_name, self.post_title, self.detail
+            ),
+            MENTION => format!(
+                "{} mentioned you in \"{}\": {}",
+                self.actor_name, self.post_title, self.detail
+            ),
+            _ if self.comment_id.is_some() => format!(
+                "{} reacted {} to your comment on \"{}\"",
+                self.actor_name, reaction, self.post_title
+            ),
+            _ => format!(
+                "{} reacted {} to \"{}\"",
+                self.actor_name, reaction, self.post_title
+            ),
+        };
+    }
+}
+
+fn insert(
+    dbconn: &Connection,
+    recipient: &str,
+    actor: &str,
+    kind: &str,
+    target: (i64, Option<i64>),
+    detail: &str,
+) {
+    // Nobody needs to be told about their own activity
+    if recipient == actor {
+        return;
+    }
+    dbconn
+        .execute(
+            "INSERT INTO notifications (email, actor, kind, post_id, comment_id, detail) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            [
+                &recipient as &dyn rusqlite::ToSql,
+                &actor as &dyn rusqlite::ToSql,
+                &kind as &dyn rusqlite::ToSql,
+                &target.0 as &dyn rusqlite::ToSql,
+                &target.1 as &dyn rusqlite::ToSql,
+                &detail as &dyn rusqlite::ToSql,
+            ],
+        )
+        .unwrap();
+}
+
+// Notifies the author of the post, or the author of the comment being replied to
+pub fn commented(dbconn: &Connection, comment_id: i64, actor: &str) {
+    let comment: Option<(i64, Option<i64>, String)> = dbconn
+        .query_row(
+            "SELECT post_id, parent_comment_id, content FROM comments WHERE comment_id = ?1",
+            [comment_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<i64>>(1)?,
+                    row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                ))
+            },
+        )
+        .ok();
+    let (post_id, parent_comment_id, content) = match comment {
+        Some(comment) => comment,
+        None => return,
+    };
+    let excerpt: String = plain_text(&content, EXCERPT_LENGTH);
+
+    let parent_author: Option<String> = parent_comment_id.and_then(|parent_comment_id: i64| {
+        dbconn
+            .query_row(
+                "SELECT email FROM comments WHERE comment_id = ?1",
+                [parent_comment_id],
+                |row| row.get(0),
+            )
+            .ok()
+    });
+    if let Some(parent_author) = &parent_author {
+        insert(
+            dbconn,
+            parent_author,
+            actor,
+            REPLY,
+            (post_id, Some(comment_id)),
+            &excerpt,
+        );
+    }
+
+    let post_author: Option<String> = dbconn
+        .query_row(
+            "SELECT email FROM posts WHERE post_id = ?1",
+            [post_id],
+            |row| row.get(0),
+        )
+        .ok();
+    if let Some(post_author) = &post_author {
+        // The reply notification already covers an author replying on their own post
+        if parent_author.as_ref() != Some(post_author) {
+            insert(
+                dbconn,
+                post_author,
+                actor,
+                COMMENT,
+                (post_id, Some(comment_id)),
+                &excerpt,
+            );
+        }
+    }
+
+    // Mentioned users that were already notified of the comment are not told twice
+    for mentioned in mentions::mentioned(dbconn, post_id, Some(comment_id)) {
+        if parent_author.as_ref() != Some(&mentioned) && post_author.as_ref() != Some(&mentioned) {
+            insert(
+                dbconn,
+                &mentioned,
+                actor,
+                MENTION,
+                (post_id, Some(comment_id)),
+                &excerpt,
+            );
+        }
+    }
+}
+
+// Notifies the users mentioned in a post, called when the post gets published
+pub fn post_mentions(dbconn: &Connection, post_id: i64) {
+    let post: Option<(String, String)> = dbconn
+        .query_row(
+            "SELECT email, content FROM posts WHERE post_id = ?1",
+            [post_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                ))
+            },
+        )
+        .ok();
+    let (author, content) = match post {
+        Some(post) => post,
+        None => return,
+    };
+    let excerpt: String = plain_text(&content, EXCERPT_LENGTH);
+    for mentioned in mentions::mentioned(dbconn, post_id, None) {
+        insert(
+            dbconn,
+            &mentioned,
+            &author,
+            MENTION,
+            (post_id, None),
+            &excerpt,
+        );
+    }
+}
+
+// Called after every reaction change, reaction is None when the reaction was removed
+pub fn reacted(dbconn: &Connection, target: &Target, actor: &str, reaction: Option<&str>) {
+    let owner: Option<(String, i64, Option<i64>)> = match target {
+        Target::Post(post_id) => dbconn
+            .query_row(
+                "SELECT email FROM posts WHERE post_id = ?1",
+                [post_id],
+                |row| Ok((row.get::<_, String>(0)?, *post_id, None)),
+            )
+            .ok(),
+        Target::Comment(comment_id) => dbconn
+            .query_row(
+                "SELECT email, post_id FROM comments WHERE comment_id = ?1",
+                [comment_id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, i64>(1)?,
+                        Some(*comment_id),
+                    ))
+                },
+            )
+            .ok(),
+    };
+    let (owner, post_id, comment_id) = match owner {
+        Some(owner) => owner,
+        None => return,
+    };
+
+    // Changing a reaction replaces the unread notification instead of piling up new ones
+    dbconn
+        .execute(
+            "DELETE FROM notifications WHERE email = ?1 AND actor = ?2 AND kind = ?3 AND post_id = ?4
+                AND comment_id IS ?5 AND read = 0",
+            [
+                &owner as &dyn rusqlite::ToSql,
+                &actor as &dyn rusqlite::ToSql,
+                &REACTION as &dyn rusqlite::ToSql,
+                &post_id as &dyn rusqlite::ToSql,
+                &comment_id as &dyn rusqlite::ToSql,
+            ],
+        )
+        .unwrap();
+    if let Some(reaction) = reaction {
+        insert(
+            dbconn,
+            &owner,
+            actor,
+            REACTION,
+            (post_id, comment_id),
+            reaction,
+        );
+    }
+}
+
+fn query(dbconn: &Connection, sql: &str, params: &[&dyn rusqlite::ToSql]) -> Vec<Notification> {
+    let mut stmt = dbconn.prepare(sql).unwrap();
+    let notifications_iter = stmt.query_map(params, |row| {
+        Ok(Notification {
+            notification_id: row.get::<_, i64>(0)?,
+            actor: row.get::<_, String>(1)?,
+            actor_name: String::new(),
+            kind: row.get::<_, String>(2)?,
+            post_id: row.get::<_, i64>(3)?,
+            post_title: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+            comment_id: row.get::<_, Option<i64>>(5)?,
+            detail: row.get::<_, String>(6)?,
+            read: row.get::<_, bool>(7)?,
+            datetime: row.get::<_, String>(8)?,
+        })
+    });
+    let mut notifications: Vec<Notification> = Vec::new();
+    for notification in notifications_iter.unwrap() {
+        let mut notification: Notification = notification.unwrap();
+        notification.actor_name = profiles::ensure(dbconn, &notification.actor).name();
+        notifications.push(notification);
+    }
+    return notifications;
+}
+
+pub fn unread_count(dbconn: &Connection, email: &str) -> i64 {
+    return dbconn
+        .query_row(
+            "SELECT COUNT(*) FROM notifications WHERE email = ?1 AND read = 0",
+            [email],
+            |row| row.get(0),
+        )
+        .unwrap();
+}
+
+pub fn digest_enabled(dbconn: &Connection, email: &str) -> bool {
+    return dbconn
+        .query_row(
+            "SELECT digest FROM users WHERE email = ?1",
+            [email],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+}
+
+pub fn set_digest(dbconn: &Connection, email: &str, enabled: bool) {
+    dbconn
+        .execute(
+            "UPDATE users SET digest = ?1 WHERE email = ?2",
+            [
+                &enabled as &dyn rusqlite::ToSql,
+                &email as &dyn rusqlite::ToSql,
+            ],
+        )
+        .unwrap();
+}
+
+// Latest notifications of the user with the unread count and the digest preference
+pub fn list(dbconn: &Connection, email: &str, unread_only: bool) -> JsonValue {
+    let notifications: Vec<Notification> = query(
+        dbconn,
+        "SELECT notifications.notification_id, notifications.actor, notifications.kind, notifications.post_id,
+                posts.title, notifications.comment_id, notifications.detail, notifications.read, notifications.datetime
+            FROM notifications LEFT JOIN posts ON posts.post_id = notifications.post_id
+            WHERE notifications.email = ?1 AND (?2 = 0 OR notifications.read = 0)
+            ORDER BY notifications.notification_id DESC LIMIT ?3",
+        &[
+            &email as &dyn rusqlite::ToSql,
+            &unread_only as &dyn rusqlite::ToSql,
+            &LIST_LENGTH as &dyn rusqlite::ToSql,
+        ],
+    );
+
+    let mut list: JsonValue = JsonValue::new_array();
+    for notification in notifications {
+        list.push(json::object! {
+            notification_id: notification.notification_id,
+            kind: notification.kind.clone(),
+            actor: profiles::author_json(dbconn, &notification.actor, false),
+            post_id: notification.post_id,
+            post_title: notification.post_title.clone(),
+            comment_id: notification.comment_id,
+            detail: notification.detail.clone(),
+            message: notification.message(),
+            read: notification.read,
+            datetime: notification.datetime + " UTC"
+        })
+        .unwrap();
+    }
+    return json::object! {
+        unread: unread_count(dbconn, email),
+        digest: digest_enabled(dbconn, email),
+        notifications: list
+    };
+}
+
+// Marks one notification as read, or all of them when notification_id is None
+pub fn mark_read(dbconn: &Connection, email: &str, notification_id: Option<i64>) {
+    dbconn
+        .execute(
+            "UPDATE notifications SET read = 1 WHERE email = ?1 AND (?2 IS NULL OR notification_id = ?2)",
+            [&email as &dyn rusqlite::ToSql, &notification_id as &dyn rusqlite::ToSql],
+        )
+        .unwrap();
+}
+
+fn send_digests(transport: &dyn MailTransport) {
+    let dbconn: Connection = dbconn();
+    let base_url: String = config::public_url(&[]);
+    let mut stmt = dbconn
+        .prepare(
+            "SELECT DISTINCT users.email FROM users JOIN notifications ON notifications.email = users.email
+                WHERE users.digest = 1 AND notifications.read = 0 AND notifications.emailed = 0",
+        )
+        .unwrap();
+    let recipients: Vec<String> = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .unwrap()
+        .map(|email| email.unwrap())
+        .collect();
+
+    for email in recipients {
+        let notifications: Vec<Notification> = query(
+            &dbconn,
+            "SELECT notifications.notification_id, notifications.actor, notifications.kind, notifications.post_id,
+                    posts.title, notifications.comment_id, notifications.detail, notifications.read, notifications.datetime
+                FROM notifications LEFT JOIN posts ON posts.post_id = notifications.post_id
+                WHERE notifications.email = ?1 AND notifications.read = 0 AND notifications.emailed = 0
+                ORDER BY notifications.notification_id ASC",
+            &[&email as &dyn rusqlite::ToSql],
+        );
+        let last_id: i64 = match notifications.last() {
+            Some(notification) => notification.notification_id,
+            None => continue,
+        };
+
+        let mut body: String = format!("You have {} new notifications:\n\n", notifications.len());
+        for notification in &notifications {
+            body.push_str(&format!(
+                "- {}\n  {}/post/{}\n",
+                notification.message(),
+                base_url,
+                notification.post_id
+            ));
+        }
+        body.push_str(&format!(
+            "\nYou can turn off these emails from the notifications menu at {}/\n",
+            base_url
+        ));
+
+        let mail: Mail = Mail {
+            to: email.clone(),
+            subject: format!("{} new notifications on Rust Blog", notifications.len()),
+            body,
+        };
+        match transport.send(&mail) {
+            Ok(()) => {
+                dbconn
+                    .execute(
+                        "UPDATE notifications SET emailed = 1 WHERE email = ?1 AND notification_id <= ?2",
+                        [&email as &dyn rusqlite::ToSql, &last_id as &dyn rusqlite::ToSql],
+                    )
+                    .unwrap();
+                println!("Sent digest to {}", email.green());
+            }
+            Err(error) => println!("Digest to {} failed: {}", email, error.to_string().red()),
+        }
+    }
+}
+
+pub async fn digest_scheduler() {
+    let mut interval = tokio::time::interval(Duration::from_secs(config::digest_interval()));
+    // The first tick completes right away, the first digest goes out after a full interval
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        // Sending mail blocks, keep it off the threads serving requests
+        let _ = tokio::task::spawn_blocking(|| send_digests(mail::transport().as_ref())).await;
+    }
+}
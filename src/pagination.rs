@@ -0,0 +1,34 @@
+use rusqlite::Connection;
+
+// Posts returned by each page of /api/feed and /api/bookmarks
+pub const PAGE_SIZE: usize = 20;
+
+// Ids of a page of posts and whether there is a next one. The query selects the post ids of the
+// user ?1, limited to ?2 from the offset ?3. Pages too far to have an offset are empty.
+pub fn post_ids(dbconn: &Connection, query: &str, email: &str, page: usize) -> (Vec<i64>, bool) {
+    let offset: i64 = match (page.max(1) - 1)
+        .checked_mul(PAGE_SIZE)
+        .and_then(|offset: usize| i64::try_from(offset).ok())
+    {
+        Some(offset) => offset,
+        None => return (Vec::new(), false),
+    };
+
+    // One more post than the page size is read to know if there is a next page
+    let mut stmt = dbconn.prepare(query).unwrap();
+    let posts_iter = stmt.query_map(
+        [
+            &email as &dyn rusqlite::ToSql,
+            &(PAGE_SIZE + 1) as &dyn rusqlite::ToSql,
+            &offset as &dyn rusqlite::ToSql,
+        ],
+        |row| row.get::<_, i64>(0),
+    );
+    let mut post_ids: Vec<i64> = posts_iter
+        .unwrap()
+        .map(|post_id| post_id.unwrap())
+        .collect();
+    let has_more: bool = post_ids.len() > PAGE_SIZE;
+    post_ids.truncate(PAGE_SIZE);
+    return (post_ids, has_more);
+}
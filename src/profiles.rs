@@ -0,0 +1,249 @@
+use json::JsonValue;
+use rusqlite::Connection;
+
+use crate::config;
+
+pub const HANDLE_MIN_LENGTH: usize = 3;
+pub const HANDLE_MAX_LENGTH: usize = 30;
+const DISPLAY_NAME_MAX_LENGTH: usize = 50;
+const BIO_MAX_LENGTH: usize = 500;
+const WEBSITE_MAX_LENGTH: usize = 200;
+
+pub enum UpdateError {
+    Invalid(String),
+    HandleTaken,
+}
+
+pub struct Profile {
+    pub user_id: i64,
+    pub email: String,
+    pub handle: String,
+    pub display_name: String,
+    pub bio: String,
+    pub website: String,
+    pub avatar: String,
+}
+
+impl Profile {
+    // The display name, or the handle for users that haven't set one
+    pub fn name(&self) -> String {
+        if self.display_name.is_empty() {
+            return self.handle.clone();
+        }
+        return self.display_name.clone();
+    }
+
+    // The uploaded avatar, or the identicon generated from the user id
+    pub fn avatar_url(&self) -> String {
+        if !self.avatar.is_empty() {
+            return self.avatar.clone();
+        }
+        return format!("/avatar/{}.svg", self.user_id);
+    }
+
+    // What everyone can see of a user, used for the authors of posts and comments
+    pub fn to_public_json(&self) -> JsonValue {
+        return json::object! {
+            user_id: self.user_id,
+            handle: self.handle.clone(),
+            display_name: self.name(),
+            avatar: self.avatar_url()
+        };
+    }
+
+    // The whole profile, only for the user themself
+    pub fn to_json(&self) -> JsonValue {
+        return json::object! {
+            user_id: self.user_id,
+            email: self.email.clone(),
+            handle: self.handle.clone(),
+            display_name: self.display_name.clone(),
+            name: self.name(),
+            bio: self.bio.clone(),
+            website: self.website.clone(),
+            avatar: self.avatar_url(),
+            uploaded_avatar: !self.avatar.is_empty()
+        };
+    }
+}
+
+fn find(dbconn: &Connection, column: &str, value: &str) -> Option<Profile> {
+    return dbconn
+        .query_row(
+            &format!(
+                "SELECT user_id, email, handle, display_name, bio, website, avatar FROM profiles WHERE {} = ?1",
+                column
+            ),
+            [value],
+            |row| {
+                Ok(Profile {
+                    user_id: row.get::<_, i64>(0)?,
+                    email: row.get::<_, String>(1)?,
+                    handle: row.get::<_, String>(2)?,
+                    display_name: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                    bio: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                    website: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+                    avatar: row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+                })
+            },
+        )
+        .ok();
+}
+
+pub fn by_handle(dbconn: &Connection, handle: &str) -> Option<Profile> {
+    return find(dbconn, "handle", &handle.to_lowercase());
+}
+
+// The profile of the user, created with a default handle the first time it is needed
+pub fn ensure(dbconn: &Connection, email: &str) -> Profile {
+    if let Some(profile) = find(dbconn, "email", email) {
+        return profile;
+    }
+
+    // The default handle is based on the id, so that it doesn't reveal anything about the email
+    dbconn
+        .execute(
+            "INSERT INTO profiles (email, handle) VALUES (?1, 'new-' || lower(hex(randomblob(8))))",
+            [email],
+        )
+        .unwrap();
+    // A handle picked before the default ones were reserved can still be taken, the random one is kept then
+    let user_id: i64 = dbconn.last_insert_rowid();
+    if let Err(err) = dbconn.execute(
+        "UPDATE profiles SET handle = 'user' || user_id WHERE user_id = ?1",
+        [user_id],
+    ) {
+        println!(
+            "Couldn't set the default handle of user {}: {}",
+            user_id, err
+        );
+    }
+    return find(dbconn, "email", email).unwrap();
+}
+
+// Public data of the author of a post, comment or reaction, the email is only added for admins
+pub fn author_json(dbconn: &Connection, email: &str, show_email: bool) -> JsonValue {
+    let mut author: JsonValue = ensure(dbconn, email).to_public_json();
+    if show_email {
+        author["email"] = email.into();
+    }
+    return author;
+}
+
+pub fn is_admin(email: &str) -> bool {
+    return config::admin_emails().contains(&email.to_lowercase());
+}
+
+// Lowercase letters, digits, "_" and "-", between 3 and 30 characters
+pub fn normalize_handle(handle: &str) -> Result<String, String> {
+    let handle: String = handle.trim().trim_start_matches('@').to_lowercase();
+    if handle.len() < HANDLE_MIN_LENGTH || handle.len() > HANDLE_MAX_LENGTH {
+        return Err(format!(
+            "The handle must be between {} and {} characters",
+            HANDLE_MIN_LENGTH, HANDLE_MAX_LENGTH
+        ));
+    }
+    if !handle
+        .chars()
+        .all(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+    {
+        return Err("The handle can only contain letters, digits, \"_\" and \"-\"".to_string());
+    }
+    // The default handles are reserved so that a new user never collides with a chosen one
+    let default_id: Option<&str> = handle.strip_prefix("user");
+    if handle.starts_with("new-")
+        || default_id
+            .is_some_and(|id: &str| !id.is_empty() && id.chars().all(|c: char| c.is_ascii_digit()))
+    {
+        return Err("This handle is reserved".to_string());
+    }
+    return Ok(handle);
+}
+
+fn check_length(field: &str, value: &str, max_length: usize) -> Result<(), String> {
+    if value.chars().count() > max_length {
+        return Err(format!(
+            "The {} can be at most {} characters",
+            field, max_length
+        ));
+    }
+    return Ok(());
+}
+
+// Applies the fields present in the JSON body of PATCH /api/users/me
+pub fn update(
+    dbconn: &Connection,
+    profile: &mut Profile,
+    changes: &JsonValue,
+) -> Result<(), UpdateError> {
+    if changes.has_key("handle") {
+        let requested: &str = changes["handle"].as_str().unwrap_or("");
+        // Keeping the current handle is always allowed, even a reserved default one
+        if requested.trim().trim_start_matches('@').to_lowercase() != profile.handle {
+            let handle: String = normalize_handle(requested).map_err(UpdateError::Invalid)?;
+            if by_handle(dbconn, &handle).is_some() {
+                return Err(UpdateError::HandleTaken);
+            }
+            profile.handle = handle;
+        }
+    }
+    if changes.has_key("display_name") {
+        let display_name: String = changes["display_name"]
+            .as_str()
+            .unwrap_or("")
+            .trim()
+            .to_string();
+        check_length("display name", &display_name, DISPLAY_NAME_MAX_LENGTH)
+            .map_err(UpdateError::Invalid)?;
+        profile.display_name = display_name;
+    }
+    if changes.has_key("bio") {
+        let bio: String = changes["bio"].as_str().unwrap_or("").trim().to_string();
+        check_length("bio", &bio, BIO_MAX_LENGTH).map_err(UpdateError::Invalid)?;
+        profile.bio = bio;
+    }
+    if changes.has_key("website") {
+        let website: String = changes["website"].as_str().unwrap_or("").trim().to_string();
+        check_length("website", &website, WEBSITE_MAX_LENGTH).map_err(UpdateError::Invalid)?;
+        if !website.is_empty()
+            && !website.starts_with("https://")
+            && !website.starts_with("http://")
+        {
+            return Err(UpdateError::Invalid(
+                "The website must be an http:// or https:// URL".to_string(),
+            ));
+        }
+        profile.website = website;
+    }
+    // The avatar is uploaded on its own, here it can only be removed
+    if changes.has_key("avatar") && changes["avatar"].is_null() {
+        profile.avatar = String::new();
+    }
+
+    dbconn
+        .execute(
+            "UPDATE profiles SET handle = ?1, display_name = ?2, bio = ?3, website = ?4, avatar = ?5 WHERE user_id = ?6",
+            [
+                &profile.handle as &dyn rusqlite::ToSql,
+                &profile.display_name as &dyn rusqlite::ToSql,
+                &profile.bio as &dyn rusqlite::ToSql,
+                &profile.website as &dyn rusqlite::ToSql,
+                &profile.avatar as &dyn rusqlite::ToSql,
+                &profile.user_id as &dyn rusqlite::ToSql,
+            ],
+        )
+        .unwrap();
+    return Ok(());
+}
+
+pub fn set_avatar(dbconn: &Connection, user_id: i64, avatar: &str) {
+    dbconn
+        .execute(
+            "UPDATE profiles SET avatar = ?1 WHERE user_id = ?2",
+            [
+                &avatar as &dyn rusqlite::ToSql,
+                &user_id as &dyn rusqlite::ToSql,
+            ],
+        )
+        .unwrap();
+}
@@ -0,0 +1,158 @@
+use colored::Colorize;
+use rusqlite::Connection;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::config;
+use crate::db::dbconn;
+use crate::events;
+use crate::http::handle_get;
+use crate::notifications;
+
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(30);
+
+pub const DRAFT: &str = "draft";
+pub const SCHEDULED: &str = "scheduled";
+pub const PUBLISHED: &str = "published";
+
+// Returns the status and the normalized publish_at ("YYYY-MM-DD HH:MM:SS" UTC) of a new post
+pub fn resolve_status(status: &str, publish_at: &str) -> Result<(String, Option<String>), String> {
+    match status {
+        "" | PUBLISHED => return Ok((PUBLISHED.to_string(), None)),
+        DRAFT => return Ok((DRAFT.to_string(), None)),
+        SCHEDULED => {}
+        _ => return Err(format!("Unknown status {}", status)),
+    }
+
+    let (publish_at, in_future): (Option<String>, bool) = dbconn()
+        .query_row(
+            "SELECT datetime(?1), datetime(?1) > CURRENT_TIMESTAMP",
+            [publish_at],
+            |row| Ok((row.get(0)?, row.get::<_, Option<bool>>(1)?.unwrap_or(false))),
+        )
+        .unwrap();
+
+    return match publish_at {
+        None => Err("Invalid publish_at".to_string()),
+        Some(_) if !in_future => Ok((PUBLISHED.to_string(), None)),
+        Some(publish_at) => Ok((SCHEDULED.to_string(), Some(publish_at))),
+    };
+}
+
+async fn send_webhook(url: &str, payload: String) -> std::io::Result<()> {
+    let address: &str = match url.strip_prefix("http://") {
+        Some(address) => address,
+        None => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "only http:// webhooks are supported",
+            ))
+        }
+    };
+    let (host, path): (&str, String) = match address.find('/') {
+        Some(index) => (&address[..index], address[index..].to_string()),
+        None => (address, "/".to_string()),
+    };
+    let host_port: String = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+
+    let mut stream: TcpStream = TcpStream::connect(host_port).await?;
+    stream
+        .write_all(
+            format!(
+                "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                path,
+                host,
+                payload.len(),
+                payload
+            )
+            .as_bytes(),
+        )
+        .await?;
+
+    let mut response: Vec<u8> = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    return Ok(());
+}
+
+// Drafts and scheduled posts are only visible to their author
+pub fn is_published(dbconn: &Connection, post_id: i64) -> bool {
+    return dbconn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM posts WHERE post_id = ?1 AND status = 'published' AND publish_at <= CURRENT_TIMESTAMP)",
+            [post_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+}
+
+// Called once for every post that becomes visible, either right away or by the scheduler
+pub fn published(dbconn: &Connection, post_id: i64) {
+    let title: String = dbconn
+        .query_row(
+            "SELECT title FROM posts WHERE post_id = ?1",
+            [post_id],
+            |row| row.get(0),
+        )
+        .unwrap_or_default();
+    println!("Published post {}: {}", post_id.to_string().green(), title);
+
+    if let Some(post) = handle_get::post_json(dbconn, post_id, false) {
+        events::emit(events::POST, post);
+    }
+    notifications::post_mentions(dbconn, post_id);
+
+    if let Some(url) = config::publish_webhook() {
+        let payload: String = json::object! {
+            event: "post_published",
+            post_id: post_id,
+            title: title
+        }
+        .dump();
+        tokio::spawn(async move {
+            if let Err(error) = send_webhook(&url, payload).await {
+                println!("Webhook {} failed: {}", url, error.to_string().red());
+            }
+        });
+    }
+}
+
+fn publish_due_posts() {
+    let dbconn: Connection = dbconn();
+    let mut stmt = dbconn
+        .prepare("SELECT post_id FROM posts WHERE status = ?1 AND publish_at <= CURRENT_TIMESTAMP")
+        .unwrap();
+    let due: Vec<i64> = stmt
+        .query_map([SCHEDULED], |row| row.get::<_, i64>(0))
+        .unwrap()
+        .map(|post_id| post_id.unwrap())
+        .collect();
+
+    for post_id in due {
+        let updated: usize = dbconn
+            .execute(
+                "UPDATE posts SET status = ?1 WHERE post_id = ?2 AND status = ?3",
+                [
+                    &PUBLISHED as &dyn rusqlite::ToSql,
+                    &post_id as &dyn rusqlite::ToSql,
+                    &SCHEDULED as &dyn rusqlite::ToSql,
+                ],
+            )
+            .unwrap();
+        if updated > 0 {
+            published(&dbconn, post_id);
+        }
+    }
+}
+
+pub async fn scheduler() {
+    let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+    loop {
+        interval.tick().await;
+        publish_due_posts();
+    }
+}
@@ -0,0 +1,201 @@
+use json::JsonValue;
+use rusqlite::Connection;
+
+use crate::config;
+use crate::profiles;
+
+const DEFAULT_REACTIONS: &str =
+    "heart:\u{2764}:Love,thumbsUp:\u{1F44D}:Like,thumbsDown:\u{1F44E}:Dislike";
+
+pub struct ReactionKind {
+    pub name: String,
+    pub emoji: String,
+    pub label: String,
+}
+
+pub enum Target {
+    Post(i64),
+    Comment(i64),
+}
+
+impl Target {
+    fn table(&self) -> &str {
+        match self {
+            Target::Post(_) => "reactions",
+            Target::Comment(_) => "comment_reactions",
+        }
+    }
+
+    fn column(&self) -> &str {
+        match self {
+            Target::Post(_) => "post_id",
+            Target::Comment(_) => "comment_id",
+        }
+    }
+
+    fn id(&self) -> i64 {
+        match self {
+            Target::Post(id) | Target::Comment(id) => *id,
+        }
+    }
+}
+
+pub fn kinds() -> Vec<ReactionKind> {
+    let reactions: String = config::reactions().unwrap_or(DEFAULT_REACTIONS.to_string());
+    let mut kinds: Vec<ReactionKind> = Vec::new();
+    for reaction in reactions.split(',') {
+        let parts: Vec<&str> = reaction.trim().splitn(3, ':').collect();
+        if parts[0].is_empty()
+            || kinds
+                .iter()
+                .any(|kind: &ReactionKind| kind.name == parts[0])
+        {
+            continue;
+        }
+        kinds.push(ReactionKind {
+            name: parts[0].to_string(),
+            emoji: parts.get(1).unwrap_or(&"").to_string(),
+            label: parts.get(2).unwrap_or(&parts[0]).to_string(),
+        });
+    }
+    return kinds;
+}
+
+pub fn kinds_json() -> JsonValue {
+    let mut kinds: JsonValue = JsonValue::new_array();
+    for kind in self::kinds() {
+        kinds
+            .push(json::object! { name: kind.name, emoji: kind.emoji, label: kind.label })
+            .unwrap();
+    }
+    return kinds;
+}
+
+pub fn is_allowed(name: &str) -> bool {
+    return kinds().iter().any(|kind: &ReactionKind| kind.name == name);
+}
+
+// Only published posts and the comments on them can get reactions
+pub fn exists(dbconn: &Connection, target: &Target) -> bool {
+    let query: &str = match target {
+        Target::Post(_) => {
+            "SELECT EXISTS(SELECT 1 FROM posts
+                WHERE post_id = ?1 AND status = 'published' AND publish_at <= CURRENT_TIMESTAMP)"
+        }
+        Target::Comment(_) => {
+            "SELECT EXISTS(SELECT 1 FROM comments JOIN posts ON posts.post_id = comments.post_id
+                WHERE comment_id = ?1 AND posts.status = 'published' AND posts.publish_at <= CURRENT_TIMESTAMP)"
+        }
+    };
+    return dbconn
+        .query_row(query, [target.id()], |row| row.get(0))
+        .unwrap();
+}
+
+pub fn counts(dbconn: &Connection, target: &Target) -> JsonValue {
+    let mut stmt = dbconn
+        .prepare(&format!(
+            "SELECT type, COUNT(type) AS count FROM {} WHERE {} = ? GROUP BY type",
+            target.table(),
+            target.column()
+        ))
+        .unwrap();
+    let reactions_iter = stmt.query_map([target.id()], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    });
+
+    let mut reactions: JsonValue = JsonValue::new_object();
+    for reaction in reactions_iter.unwrap() {
+        let reaction = reaction.unwrap();
+        reactions[reaction.0] = reaction.1.into();
+    }
+    return reactions;
+}
+
+pub fn user_reaction(dbconn: &Connection, target: &Target, email: &str) -> Option<String> {
+    return dbconn
+        .query_row(
+            &format!(
+                "SELECT type FROM {} WHERE {} = ?1 AND email = ?2",
+                target.table(),
+                target.column()
+            ),
+            [
+                &target.id() as &dyn rusqlite::ToSql,
+                &email as &dyn rusqlite::ToSql,
+            ],
+            |row| row.get(0),
+        )
+        .ok();
+}
+
+pub fn remove(dbconn: &Connection, target: &Target, email: &str) -> bool {
+    let removed: usize = dbconn
+        .execute(
+            &format!(
+                "DELETE FROM {} WHERE {} = ?1 AND email = ?2",
+                target.table(),
+                target.column()
+            ),
+            [
+                &target.id() as &dyn rusqlite::ToSql,
+                &email as &dyn rusqlite::ToSql,
+            ],
+        )
+        .unwrap();
+    return removed > 0;
+}
+
+// Sets the reaction of the user, reacting again with the same kind removes it.
+// Returns the reaction the user has afterwards.
+pub fn toggle(dbconn: &Connection, target: &Target, email: &str, kind: &str) -> Option<String> {
+    let current: Option<String> = user_reaction(dbconn, target, email);
+    remove(dbconn, target, email);
+    if current.as_deref() == Some(kind) {
+        return None;
+    }
+
+    dbconn
+        .execute(
+            &format!(
+                "INSERT INTO {} (email, {}, type) VALUES (?1, ?2, ?3)",
+                target.table(),
+                target.column()
+            ),
+            [
+                &email as &dyn rusqlite::ToSql,
+                &target.id() as &dyn rusqlite::ToSql,
+                &kind as &dyn rusqlite::ToSql,
+            ],
+        )
+        .unwrap();
+    return Some(kind.to_string());
+}
+
+// Who reacted to the target, the emails of the users are only included for admins
+pub fn reactors(dbconn: &Connection, target: &Target, show_emails: bool) -> JsonValue {
+    let mut stmt = dbconn
+        .prepare(&format!(
+            "SELECT email, type, datetime FROM {} WHERE {} = ? ORDER BY datetime DESC",
+            target.table(),
+            target.column()
+        ))
+        .unwrap();
+    let reactors_iter = stmt.query_map([target.id()], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    });
+
+    let mut reactors: JsonValue = JsonValue::new_array();
+    for reactor in reactors_iter.unwrap() {
+        let reactor = reactor.unwrap();
+        let mut json: JsonValue = profiles::author_json(dbconn, &reactor.0, show_emails);
+        json["type"] = reactor.1.into();
+        json["datetime"] = (reactor.2 + " UTC").into();
+        reactors.push(json).unwrap();
+    }
+    return reactors;
+}
@@ -0,0 +1,82 @@
+use json::JsonValue;
+use rusqlite::Connection;
+
+const MAX_TAG_LENGTH: usize = 32;
+const MAX_TAGS_PER_POST: usize = 10;
+
+pub fn normalize(tag: &str) -> String {
+    let tag: String = tag
+        .trim()
+        .trim_start_matches('#')
+        .to_lowercase()
+        .replace(' ', "-")
+        .chars()
+        .filter(|c: &char| c.is_alphanumeric() || *c == '-' || *c == '_')
+        .take(MAX_TAG_LENGTH)
+        .collect();
+    return tag;
+}
+
+pub fn parse(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split([',', '\n']) {
+        let tag: String = normalize(tag);
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags.truncate(MAX_TAGS_PER_POST);
+    return tags;
+}
+
+pub fn save(dbconn: &Connection, post_id: i64, tags: &[String]) {
+    for tag in tags {
+        dbconn
+            .execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [tag])
+            .unwrap();
+        dbconn
+            .execute(
+                "INSERT OR IGNORE INTO post_tags (post_id, tag_id) SELECT ?1, tag_id FROM tags WHERE name = ?2",
+                [&post_id as &dyn rusqlite::ToSql, tag as &dyn rusqlite::ToSql],
+            )
+            .unwrap();
+    }
+}
+
+pub fn of_post(dbconn: &Connection, post_id: i64) -> JsonValue {
+    let mut stmt = dbconn
+        .prepare(
+            "SELECT tags.name FROM post_tags JOIN tags ON tags.tag_id = post_tags.tag_id WHERE post_tags.post_id = ? ORDER BY tags.name",
+        )
+        .unwrap();
+    let tags_iter = stmt.query_map([post_id], |row| row.get::<_, String>(0));
+
+    let mut tags: JsonValue = JsonValue::new_array();
+    for tag in tags_iter.unwrap() {
+        tags.push(tag.unwrap()).unwrap();
+    }
+    return tags;
+}
+
+pub fn with_counts(dbconn: &Connection) -> JsonValue {
+    let mut stmt = dbconn
+        .prepare(
+            "SELECT tags.name, COUNT(post_tags.post_id) AS count FROM tags
+                JOIN post_tags ON post_tags.tag_id = tags.tag_id
+                JOIN posts ON posts.post_id = post_tags.post_id
+                WHERE posts.status = 'published' AND posts.publish_at <= CURRENT_TIMESTAMP
+                GROUP BY tags.tag_id ORDER BY count DESC, tags.name",
+        )
+        .unwrap();
+    let tags_iter = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    });
+
+    let mut tags: JsonValue = JsonValue::new_array();
+    for tag in tags_iter.unwrap() {
+        let tag = tag.unwrap();
+        tags.push(json::object! { name: tag.0, count: tag.1 })
+            .unwrap();
+    }
+    return tags;
+}